@@ -0,0 +1,451 @@
+use crate::{
+    floodfill::reachable_cells,
+    models::{Battlesnake, Board, Coord},
+    utils,
+};
+
+// The cells nobody occupies as a graph, neighbouring cells are connected.
+// Bodies are walls, except for tails that move out of the way next turn.
+pub struct BoardGraph {
+    width: usize,
+    height: usize,
+    free: Vec<bool>,
+    // Connected region of every free cell.
+    regions: Vec<Option<usize>>,
+    region_sizes: Vec<usize>,
+    // Biconnected components, cells shared by several of them are
+    // articulation points.
+    components: Vec<Vec<usize>>,
+    articulation: Vec<bool>,
+}
+
+// A move onto an articulation point.
+#[derive(Debug)]
+pub struct CutMove {
+    pub dir: (i32, i32),
+    pub cell: Coord,
+    // Sizes of the pieces the region falls apart into, largest first.
+    pub pieces: Vec<usize>,
+}
+
+impl BoardGraph {
+    pub fn new(board: &Board) -> Self {
+        let width = board.width as usize;
+        let height = board.height as usize;
+        let mut free = vec![true; width * height];
+        for (_, snake) in board.live_snakes() {
+            let len = snake.body.len();
+            for (index, coord) in snake.body.iter().enumerate() {
+                let stacked = index > 0 && snake.body[index - 1] == *coord;
+                if index == len - 1 && len > 1 && !stacked {
+                    continue;
+                }
+                free[coord.y() * width + coord.x()] = false;
+            }
+        }
+
+        let mut graph = Self {
+            width,
+            height,
+            free,
+            regions: vec![None; width * height],
+            region_sizes: vec![],
+            components: vec![],
+            articulation: vec![false; width * height],
+        };
+        graph.find_regions();
+        graph.find_components();
+        graph
+    }
+
+    pub fn is_articulation_point(&self, coord: &Coord) -> bool {
+        self.index(coord)
+            .map(|cell| self.articulation[cell])
+            .unwrap_or(false)
+    }
+
+    pub fn region_size(&self, region: usize) -> usize {
+        self.region_sizes[region]
+    }
+
+    // Regions the snake can move into next.
+    pub fn reachable_regions(&self, snake: &Battlesnake) -> Vec<usize> {
+        let mut regions: Vec<usize> = self
+            .free_neighbours(&snake.head)
+            .filter_map(|cell| self.regions[cell])
+            .collect();
+        regions.sort_unstable();
+        regions.dedup();
+        regions
+    }
+
+    // No other snake can get to a cell this one can get to, not even once
+    // the bodies in between have moved out of the way.
+    pub fn is_isolated(&self, board: &Board, snake: &Battlesnake) -> bool {
+        if self.reachable_regions(snake).is_empty() {
+            return false;
+        }
+        let ours = reachable_cells(board, &snake.id);
+        board
+            .live_snakes()
+            .filter(|(_, other)| other.id != snake.id)
+            .all(|(_, other)| {
+                reachable_cells(board, &other.id)
+                    .iter()
+                    .zip(&ours)
+                    .all(|(theirs, ours)| !(*theirs && *ours))
+            })
+    }
+
+    // Moves of the snake that would split the region they enter.
+    pub fn cut_moves(&self, snake: &Battlesnake) -> Vec<CutMove> {
+        utils::DIRECTIONS
+            .iter()
+            .filter_map(|dir| {
+                let cell = Coord {
+                    x: snake.head.x + dir.1,
+                    y: snake.head.y + dir.0,
+                };
+                if !self.is_articulation_point(&cell) {
+                    return None;
+                }
+                let pieces = self.pieces_without(&cell);
+                Some(CutMove {
+                    dir: *dir,
+                    cell,
+                    pieces,
+                })
+            })
+            .collect()
+    }
+
+    // Whether another snake can take a chokepoint that leaves this one too
+    // little room for its body.
+    pub fn can_be_cut_off(&self, board: &Board, snake: &Battlesnake) -> bool {
+        let regions = self.reachable_regions(snake);
+        let room = regions
+            .iter()
+            .map(|region| self.region_size(*region))
+            .max()
+            .unwrap_or(0);
+        // Already boxed in, which the flood fill judges better.
+        if room < snake.body.len() {
+            return false;
+        }
+        board
+            .snakes
+            .iter()
+            .filter(|other| {
+                other.id != snake.id && other.eliminated_cause.is_none()
+            })
+            .flat_map(|other| self.cut_moves(other))
+            // Gaps elsewhere on the board can't cut us off.
+            .filter(|cut| {
+                let region =
+                    self.index(&cut.cell).and_then(|cell| self.regions[cell]);
+                matches!(region, Some(region) if regions.contains(&region))
+            })
+            .any(|cut| self.traps(&cut, snake))
+    }
+
+    // Moves of the snake that cut another one off, the other way around.
+    pub fn trapping_moves(
+        &self,
+        board: &Board,
+        snake: &Battlesnake,
+    ) -> Vec<(i32, i32)> {
+        self.cut_moves(snake)
+            .into_iter()
+            .filter(|cut| {
+                board.snakes.iter().any(|other| {
+                    other.id != snake.id
+                        && other.eliminated_cause.is_none()
+                        && self.traps(cut, other)
+                })
+            })
+            .map(|cut| cut.dir)
+            .collect()
+    }
+
+    // The snake is left with a piece smaller than its body once `cut` is
+    // taken.
+    fn traps(&self, cut: &CutMove, snake: &Battlesnake) -> bool {
+        let smallest = cut.pieces.last().copied().unwrap_or(0);
+        if smallest >= snake.body.len() {
+            return false;
+        }
+        let room = self.room_after_cut(&snake.head, &cut.cell);
+        room > 0 && room < snake.body.len()
+    }
+
+    // Largest piece we can still move into once `cut` is taken.
+    fn room_after_cut(&self, head: &Coord, cut: &Coord) -> usize {
+        let Some(cut) = self.index(cut) else {
+            return 0;
+        };
+        self.free_neighbours(head)
+            .filter(|cell| *cell != cut)
+            .map(|cell| self.fill(cell, cut).len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn pieces_without(&self, coord: &Coord) -> Vec<usize> {
+        let Some(cut) = self.index(coord) else {
+            return vec![];
+        };
+        let mut seen = vec![false; self.free.len()];
+        let mut pieces = vec![];
+        for start in self.neighbours(cut) {
+            if !self.free[start] || seen[start] {
+                continue;
+            }
+            let piece = self.fill(start, cut);
+            for cell in &piece {
+                seen[*cell] = true;
+            }
+            pieces.push(piece.len());
+        }
+        pieces.sort_unstable_by(|a, b| b.cmp(a));
+        pieces
+    }
+
+    // Free cells connected to `start` without going through `blocked`.
+    fn fill(&self, start: usize, blocked: usize) -> Vec<usize> {
+        let mut seen = vec![false; self.free.len()];
+        seen[start] = true;
+        seen[blocked] = true;
+        let mut cells = vec![start];
+        let mut next = 0;
+        while next < cells.len() {
+            for neighbour in self.neighbours(cells[next]) {
+                if self.free[neighbour] && !seen[neighbour] {
+                    seen[neighbour] = true;
+                    cells.push(neighbour);
+                }
+            }
+            next += 1;
+        }
+        cells
+    }
+
+    fn find_regions(&mut self) {
+        for start in 0..self.free.len() {
+            if !self.free[start] || self.regions[start].is_some() {
+                continue;
+            }
+            let region = self.region_sizes.len();
+            let mut stack = vec![start];
+            self.regions[start] = Some(region);
+            let mut size = 0;
+            while let Some(cell) = stack.pop() {
+                size += 1;
+                for neighbour in self.neighbours(cell) {
+                    if self.free[neighbour] && self.regions[neighbour].is_none()
+                    {
+                        self.regions[neighbour] = Some(region);
+                        stack.push(neighbour);
+                    }
+                }
+            }
+            self.region_sizes.push(size);
+        }
+    }
+
+    fn find_components(&mut self) {
+        let mut tarjan = Tarjan {
+            discovery: vec![0; self.free.len()],
+            low: vec![0; self.free.len()],
+            time: 0,
+            edges: vec![],
+        };
+        for cell in 0..self.free.len() {
+            if self.free[cell] && tarjan.discovery[cell] == 0 {
+                self.visit(&mut tarjan, cell, None);
+            }
+        }
+
+        let mut memberships = vec![0; self.free.len()];
+        for component in &self.components {
+            for cell in component {
+                memberships[*cell] += 1;
+            }
+        }
+        for (cell, count) in memberships.into_iter().enumerate() {
+            self.articulation[cell] = count > 1;
+        }
+    }
+
+    // Tarjan's biconnected components, edges wait on a stack until the
+    // component they belong to is complete.
+    fn visit(
+        &mut self,
+        tarjan: &mut Tarjan,
+        cell: usize,
+        parent: Option<usize>,
+    ) {
+        tarjan.time += 1;
+        tarjan.discovery[cell] = tarjan.time;
+        tarjan.low[cell] = tarjan.time;
+        for neighbour in self.neighbours(cell) {
+            if !self.free[neighbour] {
+                continue;
+            }
+            if tarjan.discovery[neighbour] == 0 {
+                tarjan.edges.push((cell, neighbour));
+                self.visit(tarjan, neighbour, Some(cell));
+                tarjan.low[cell] = tarjan.low[cell].min(tarjan.low[neighbour]);
+                if tarjan.low[neighbour] >= tarjan.discovery[cell] {
+                    self.components.push(tarjan.pop_component(cell, neighbour));
+                }
+            } else if Some(neighbour) != parent
+                && tarjan.discovery[neighbour] < tarjan.discovery[cell]
+            {
+                tarjan.edges.push((cell, neighbour));
+                tarjan.low[cell] =
+                    tarjan.low[cell].min(tarjan.discovery[neighbour]);
+            }
+        }
+    }
+
+    fn index(&self, coord: &Coord) -> Option<usize> {
+        if !coord.in_bounds(self.width as i32, self.height as i32) {
+            return None;
+        }
+        Some(coord.y() * self.width + coord.x())
+    }
+
+    // Cells next to `cell`, without borrowing the graph so they can be
+    // visited while it is being built.
+    fn neighbours(&self, cell: usize) -> impl Iterator<Item = usize> {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let (x, y) = ((cell as i32) % width, (cell as i32) / width);
+        utils::DIRECTIONS.iter().filter_map(move |(dy, dx)| {
+            let next = Coord {
+                x: x + dx,
+                y: y + dy,
+            };
+            next.in_bounds(width, height)
+                .then(|| next.y() * width as usize + next.x())
+        })
+    }
+
+    fn free_neighbours<'a>(
+        &'a self,
+        coord: &Coord,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let coord = coord.clone();
+        utils::DIRECTIONS.iter().filter_map(move |(y, x)| {
+            self.index(&Coord {
+                x: coord.x + x,
+                y: coord.y + y,
+            })
+            .filter(|cell| self.free[*cell])
+        })
+    }
+}
+
+struct Tarjan {
+    // Order cells were first visited in, 0 if not yet.
+    discovery: Vec<usize>,
+    // Earliest discovered cell reachable through a back edge.
+    low: Vec<usize>,
+    time: usize,
+    edges: Vec<(usize, usize)>,
+}
+
+impl Tarjan {
+    fn pop_component(&mut self, from: usize, to: usize) -> Vec<usize> {
+        let mut cells = vec![];
+        while let Some(edge) = self.edges.pop() {
+            cells.push(edge.0);
+            cells.push(edge.1);
+            if edge == (from, to) {
+                break;
+            }
+        }
+        cells.sort_unstable();
+        cells.dedup();
+        cells
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::scenarios::{get_board, made_up_board, place};
+
+    // A 5x3 board split in two halves by a wall with a gap at (2, 1). We
+    // are on the left and "post" sits right below the gap.
+    fn gap_board() -> Board {
+        made_up_board(
+            5,
+            3,
+            &[
+                ("you", &[(1, 0), (0, 0), (0, 1), (0, 2), (1, 2)]),
+                ("post", &[(2, 0), (2, 0)]),
+                ("wall", &[(2, 2), (2, 2)]),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_gap_is_articulation_point() {
+        let board = gap_board();
+        let graph = BoardGraph::new(&board);
+        assert!(graph.is_articulation_point(&Coord { x: 2, y: 1 }));
+        assert!(!graph.is_articulation_point(&Coord { x: 4, y: 1 }));
+        assert!(!graph.is_articulation_point(&Coord { x: 0, y: 0 }));
+
+        // Our tail moves away, so (1, 1), (1, 2), the gap and the right side.
+        let regions = graph.reachable_regions(&board.snakes[0]);
+        assert_eq!(regions.len(), 1);
+        assert_eq!(graph.region_size(regions[0]), 9);
+        assert!(!graph.is_isolated(&board, &board.snakes[0]));
+
+        // The gap closed by a body, but its tail is gone by the time we
+        // get there.
+        let mut board = board;
+        let close_gap = |board: &mut Board, tail: usize| {
+            let mut body = vec![(2, 0), (2, 1)];
+            body.resize(2 + tail, (2, 2));
+            place(board, 1, &body);
+        };
+        board.snakes.truncate(2);
+        close_gap(&mut board, 2);
+        let graph = BoardGraph::new(&board);
+        assert!(!graph.is_isolated(&board, &board.snakes[0]));
+
+        // Having just eaten a lot the gap stays closed, we are on our own.
+        close_gap(&mut board, 6);
+        let graph = BoardGraph::new(&board);
+        assert!(graph.is_isolated(&board, &board.snakes[0]));
+    }
+
+    #[test]
+    fn test_cut_off_through_the_gap() {
+        let board = gap_board();
+        let graph = BoardGraph::new(&board);
+        let cuts = graph.cut_moves(&board.snakes[1]);
+        assert_eq!(cuts.len(), 1);
+        assert_eq!(cuts[0].dir, (1, 0));
+        assert_eq!(cuts[0].pieces, [6, 2]);
+
+        // Two cells are left for a body of five.
+        assert!(graph.can_be_cut_off(&board, &board.snakes[0]));
+        assert!(!graph.can_be_cut_off(&board, &board.snakes[1]));
+        assert_eq!(graph.trapping_moves(&board, &board.snakes[1]), [(1, 0)]);
+        assert!(graph.trapping_moves(&board, &board.snakes[0]).is_empty());
+    }
+
+    #[test]
+    fn test_open_board_has_no_chokepoints() {
+        let board = get_board().board;
+        let graph = BoardGraph::new(&board);
+        for snake in &board.snakes {
+            assert!(!graph.can_be_cut_off(&board, snake));
+            assert!(graph.trapping_moves(&board, snake).is_empty());
+        }
+    }
+}
@@ -1,12 +1,166 @@
-#[derive(Clone)]
+use crate::utils;
+
+// How positions are scored, see `evaluation`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EvaluatorKind {
+    // Reachable area, length and health.
+    Fill,
+    // Body length only.
+    Length,
+    // Cells, food reached first and length.
+    Voronoi,
+}
+
+impl EvaluatorKind {
+    const FILL: &str = "fill";
+    const LENGTH: &str = "length";
+    const VORONOI: &str = "voronoi";
+
+    pub fn parse(evaluator: &str) -> Self {
+        match evaluator {
+            EvaluatorKind::FILL => EvaluatorKind::Fill,
+            EvaluatorKind::LENGTH => EvaluatorKind::Length,
+            EvaluatorKind::VORONOI => EvaluatorKind::Voronoi,
+            _ => panic!("Invalid evaluator configured"),
+        }
+    }
+}
+
+// How MCTS spreads its search over threads.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Parallelism {
+    // Independent trees whose root statistics are added up.
+    Root,
+    // One tree searched by every thread.
+    Tree,
+}
+
+impl Parallelism {
+    const ROOT: &str = "root";
+    const TREE: &str = "tree";
+
+    pub fn parse(parallelism: &str) -> Self {
+        match parallelism {
+            Parallelism::ROOT => Parallelism::Root,
+            Parallelism::TREE => Parallelism::Tree,
+            _ => panic!("Invalid parallelism configured"),
+        }
+    }
+}
+
+// How MCTS deals with snakes moving at the same time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TreePolicy {
+    // Snakes take turns, each one sees the moves made before it.
+    Sequential,
+    // Every snake picks its move at the same node from its own
+    // statistics (decoupled UCT).
+    Decoupled,
+}
+
+impl TreePolicy {
+    const SEQUENTIAL: &str = "sequential";
+    const DECOUPLED: &str = "decoupled";
+
+    pub fn parse(policy: &str) -> Self {
+        match policy {
+            TreePolicy::SEQUENTIAL => TreePolicy::Sequential,
+            TreePolicy::DECOUPLED => TreePolicy::Decoupled,
+            _ => panic!("Invalid tree policy configured"),
+        }
+    }
+}
+
+// How MCTS playouts pick moves, see `montecarlo::rollout`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RolloutKind {
+    // Any move that does not kill the snake right away.
+    Random,
+    // Keeps room to move, stays away from bigger heads and eats when
+    // hungry.
+    Heuristic,
+}
+
+impl RolloutKind {
+    const RANDOM: &str = "random";
+    const HEURISTIC: &str = "heuristic";
+
+    pub fn parse(rollout: &str) -> Self {
+        match rollout {
+            RolloutKind::RANDOM => RolloutKind::Random,
+            RolloutKind::HEURISTIC => RolloutKind::Heuristic,
+            _ => panic!("Invalid rollout policy configured"),
+        }
+    }
+}
+
+// How MCTS picks the move to make from the root statistics, see
+// `montecarlo::selection`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MoveSelection {
+    // The most visited move.
+    Robust,
+    // The move with the best win rate.
+    Max,
+    // The move with the best lower confidence bound on its win rate.
+    Secure,
+    // The move with the most wins, each tree's visits weighted by its win
+    // rate.
+    Weighted,
+}
+
+impl MoveSelection {
+    const ROBUST: &str = "robust";
+    const MAX: &str = "max";
+    const SECURE: &str = "secure";
+    const WEIGHTED: &str = "weighted";
+
+    pub fn parse(selection: &str) -> Self {
+        match selection {
+            MoveSelection::ROBUST => MoveSelection::Robust,
+            MoveSelection::MAX => MoveSelection::Max,
+            MoveSelection::SECURE => MoveSelection::Secure,
+            MoveSelection::WEIGHTED => MoveSelection::Weighted,
+            _ => panic!("Invalid move selection configured"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub struct MonteCarloConfig {
     pub iterations: i64,
     pub max_duration: u64,
+    // Biases selection towards children it scores well.
+    pub evaluator: EvaluatorKind,
+    pub parallelism: Parallelism,
+    // Threads searching the shared tree, root parallelization runs one
+    // tree per thread instead.
+    pub threads: usize,
+    pub tree_policy: TreePolicy,
+    pub rollout: RolloutKind,
+    // Chance of a random move instead of the heuristic one in playouts.
+    pub rollout_epsilon: f64,
+    // Turns after which a playout is scored by the evaluator instead of
+    // played to the end, 0 for no limit.
+    pub rollout_depth: usize,
+    // Visits after which a move's own statistics count as much as its RAVE
+    // statistics, 0 for plain UCT.
+    pub rave_equivalence: f64,
+    pub move_selection: MoveSelection,
 }
 
 impl MonteCarloConfig {
     const ITERATIONS: &str = "MONTE_CARLO_ITERATIONS";
     const MAX_DURATION: &str = "MONTE_CARLO_MAX_DURATION";
+    const EVALUATOR: &str = "MONTE_CARLO_EVALUATOR";
+    const PARALLELISM: &str = "MONTE_CARLO_PARALLELISM";
+    const THREADS: &str = "MONTE_CARLO_THREADS";
+    const TREE_POLICY: &str = "MONTE_CARLO_TREE_POLICY";
+    const ROLLOUT: &str = "MONTE_CARLO_ROLLOUT";
+    const ROLLOUT_EPSILON: &str = "MONTE_CARLO_ROLLOUT_EPSILON";
+    const ROLLOUT_DEPTH: &str = "MONTE_CARLO_ROLLOUT_DEPTH";
+    const RAVE_EQUIVALENCE: &str = "MONTE_CARLO_RAVE_EQUIVALENCE";
+    const MOVE_SELECTION: &str = "MONTE_CARLO_MOVE_SELECTION";
     pub fn load() -> Self {
         Self {
             iterations: std::env::var(MonteCarloConfig::ITERATIONS)
@@ -17,6 +171,39 @@ impl MonteCarloConfig {
                 .unwrap_or("400".to_string())
                 .parse()
                 .unwrap_or(400),
+            evaluator: EvaluatorKind::parse(
+                &std::env::var(MonteCarloConfig::EVALUATOR)
+                    .unwrap_or(EvaluatorKind::LENGTH.to_string()),
+            ),
+            parallelism: Parallelism::parse(
+                &std::env::var(MonteCarloConfig::PARALLELISM)
+                    .unwrap_or(Parallelism::TREE.to_string()),
+            ),
+            threads: utils::read_env(
+                MonteCarloConfig::THREADS,
+                rayon::current_num_threads(),
+            ),
+            tree_policy: TreePolicy::parse(
+                &std::env::var(MonteCarloConfig::TREE_POLICY)
+                    .unwrap_or(TreePolicy::SEQUENTIAL.to_string()),
+            ),
+            rollout: RolloutKind::parse(
+                &std::env::var(MonteCarloConfig::ROLLOUT)
+                    .unwrap_or(RolloutKind::RANDOM.to_string()),
+            ),
+            rollout_epsilon: utils::read_env(
+                MonteCarloConfig::ROLLOUT_EPSILON,
+                0.1,
+            ),
+            rollout_depth: utils::read_env(MonteCarloConfig::ROLLOUT_DEPTH, 40),
+            rave_equivalence: utils::read_env(
+                MonteCarloConfig::RAVE_EQUIVALENCE,
+                0.0,
+            ),
+            move_selection: MoveSelection::parse(
+                &std::env::var(MonteCarloConfig::MOVE_SELECTION)
+                    .unwrap_or(MoveSelection::ROBUST.to_string()),
+            ),
         }
     }
 
@@ -25,27 +212,110 @@ impl MonteCarloConfig {
         Self {
             iterations: 6000,
             max_duration: 100,
+            evaluator: EvaluatorKind::Length,
+            parallelism: Parallelism::Root,
+            threads: 1,
+            tree_policy: TreePolicy::Sequential,
+            rollout: RolloutKind::Random,
+            rollout_epsilon: 0.0,
+            rollout_depth: 0,
+            rave_equivalence: 0.0,
+            move_selection: MoveSelection::Robust,
+        }
+    }
+}
+// How minimax searches games with more than two snakes alive. Duels use
+// negamax unless the simultaneous search is asked for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SearchStrategy {
+    // Every snake maximizes its own score.
+    MaxN,
+    // Every opponent minimizes our score.
+    Paranoid,
+    // Only the single strongest opponent reply is searched each turn.
+    BestReply,
+    // Our move against the worst joint reply of all opponents.
+    Simultaneous,
+}
+
+impl SearchStrategy {
+    const MAX_N: &str = "max_n";
+    const PARANOID: &str = "paranoid";
+    const BEST_REPLY: &str = "best_reply";
+    const SIMULTANEOUS: &str = "simultaneous";
+
+    pub fn parse(strategy: &str) -> Self {
+        match strategy {
+            SearchStrategy::MAX_N => SearchStrategy::MaxN,
+            SearchStrategy::PARANOID => SearchStrategy::Paranoid,
+            SearchStrategy::BEST_REPLY => SearchStrategy::BestReply,
+            SearchStrategy::SIMULTANEOUS => SearchStrategy::Simultaneous,
+            _ => panic!("Invalid search strategy configured"),
         }
     }
 }
+
 pub struct MiniMaxConfig {
+    // Deepest iteration iterative deepening is allowed to reach.
     pub depth: usize,
+    pub max_duration: u64,
+    // Number of positions the transposition table can hold.
+    pub transposition_size: usize,
+    pub strategy: SearchStrategy,
+    // Threads searching in parallel, sharing the transposition table.
+    pub threads: usize,
+    // Scores the leaves.
+    pub evaluator: EvaluatorKind,
 }
 
 impl MiniMaxConfig {
     const MINIMAX_DEPTH: &str = "MINIMAX_DEPTH";
+    const MAX_DURATION: &str = "MINIMAX_MAX_DURATION";
+    const TRANSPOSITION_SIZE: &str = "MINIMAX_TRANSPOSITION_SIZE";
+    const STRATEGY: &str = "MINIMAX_STRATEGY";
+    const THREADS: &str = "MINIMAX_THREADS";
+    const EVALUATOR: &str = "MINIMAX_EVALUATOR";
     pub fn load() -> Self {
         Self {
             depth: std::env::var(MiniMaxConfig::MINIMAX_DEPTH)
                 .unwrap()
                 .parse()
                 .unwrap(),
+            max_duration: std::env::var(MiniMaxConfig::MAX_DURATION)
+                .unwrap_or("400".to_string())
+                .parse()
+                .unwrap_or(400),
+            transposition_size: utils::read_env(
+                MiniMaxConfig::TRANSPOSITION_SIZE,
+                1 << 18,
+            ),
+            strategy: SearchStrategy::parse(
+                &std::env::var(MiniMaxConfig::STRATEGY)
+                    .unwrap_or(SearchStrategy::MAX_N.to_string()),
+            ),
+            threads: utils::read_env(
+                MiniMaxConfig::THREADS,
+                rayon::current_num_threads(),
+            ),
+            evaluator: EvaluatorKind::parse(
+                &std::env::var(MiniMaxConfig::EVALUATOR)
+                    .unwrap_or(EvaluatorKind::FILL.to_string()),
+            ),
         }
     }
 
+    // Generous time budget so the scenario tests always reach full depth.
     #[cfg(test)]
     pub fn default() -> Self {
-        Self { depth: 11 }
+        Self {
+            depth: 11,
+            max_duration: 60_000,
+            transposition_size: 1 << 18,
+            strategy: SearchStrategy::MaxN,
+            // Single threaded so scenario results are reproducible.
+            threads: 1,
+            evaluator: EvaluatorKind::Fill,
+        }
     }
 }
 
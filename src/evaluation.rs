@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use crate::{
+    config::EvaluatorKind,
+    floodfill::{floodfill, floodfill_within},
+    models::{Battlesnake, Board},
+    pathfinding::{food_in_time, HUNGRY},
+    simulation::EndState,
+    voronoi::voronoi,
+};
+
+// Values positions for both engines. Implementations only score snakes in
+// a game that is still going, `evaluate` settles finished games.
+pub trait Evaluator: Send + Sync {
+    // Raw value of a snake, only comparable to the other snakes on the
+    // same board.
+    fn score(&self, board: &Board, snake: &Battlesnake) -> f32;
+
+    // Raw values of every snake still alive, dead snakes get nothing.
+    // Evaluators that look at all snakes at once override this.
+    fn scores(&self, board: &Board) -> Vec<f32> {
+        board
+            .snakes
+            .iter()
+            .map(|snake| match snake.eliminated_cause {
+                Some(_) => 0.0,
+                None => self.score(board, snake),
+            })
+            .collect()
+    }
+
+    // Every snake's share of the board, between 0 and 1 and adding up to at
+    // most 1. The winner takes everything, dead snakes get nothing and
+    // neither do snakes that are going to starve.
+    fn evaluate(&self, board: &Board) -> Vec<f32> {
+        let scores: Vec<f32> = match board.get_endstate() {
+            EndState::Playing => self
+                .scores(board)
+                .into_iter()
+                .zip(&board.snakes)
+                .map(|(score, snake)| score * hunger_factor(board, snake))
+                .collect(),
+            EndState::Winner(winner) => board
+                .snakes
+                .iter()
+                .map(|snake| if snake.id == winner { 1.0 } else { 0.0 })
+                .collect(),
+            EndState::Tie => vec![0.0; board.snakes.len()],
+        };
+        let total: f32 = scores.iter().sum();
+        if total == 0.0 {
+            return scores;
+        }
+        scores.iter().map(|score| score / total).collect()
+    }
+}
+
+// With fewer turns to spare on the way to food a position gets worse.
+const FOOD_SLACK: usize = 10;
+
+// Scales a snake's value by how safely it can eat. A hungry snake that
+// can't get to any food before starving, or before other snakes eat it,
+// is as good as dead. Food that may still appear is not counted on, so
+// with no food on the board a hungry snake is lost too.
+fn hunger_factor(board: &Board, snake: &Battlesnake) -> f32 {
+    if snake.health > HUNGRY || snake.eliminated_cause.is_some() {
+        return 1.0;
+    }
+    let Some(path) = food_in_time(board, &snake.id) else {
+        return 0.0;
+    };
+    let slack = (snake.health as usize).saturating_sub(path.turns);
+    if slack >= FOOD_SLACK {
+        return 1.0;
+    }
+    0.5 + 0.5 * slack as f32 / FOOD_SLACK as f32
+}
+
+pub fn build(kind: EvaluatorKind) -> Arc<dyn Evaluator> {
+    match kind {
+        EvaluatorKind::Fill => Arc::new(FillEvaluator::default()),
+        EvaluatorKind::Length => Arc::new(LengthEvaluator),
+        EvaluatorKind::Voronoi => Arc::new(VoronoiEvaluator::default()),
+    }
+}
+
+// Room to move plus length and health. Room within reach of the next few
+// moves breaks ties between positions with the same room overall.
+pub struct FillEvaluator {
+    pub fill_weight: f32,
+    pub nearby_weight: f32,
+    pub health_weight: f32,
+    pub length_weight: f32,
+}
+
+impl Default for FillEvaluator {
+    fn default() -> Self {
+        Self {
+            fill_weight: 4.5,
+            nearby_weight: 1.0,
+            health_weight: 0.0,
+            length_weight: 10.0,
+        }
+    }
+}
+
+impl FillEvaluator {
+    const NEARBY_MOVES: usize = 3;
+}
+
+impl Evaluator for FillEvaluator {
+    fn score(&self, board: &Board, snake: &Battlesnake) -> f32 {
+        let fill_score = floodfill(board, &snake.id);
+        let mut final_score = (snake.health as f32) * self.health_weight;
+        final_score += (snake.body.len() as f32) * self.length_weight;
+        final_score += (fill_score as f32) * self.fill_weight;
+        let nearby =
+            floodfill_within(board, &snake.id, FillEvaluator::NEARBY_MOVES);
+        final_score += (nearby as f32) * self.nearby_weight;
+        final_score
+    }
+}
+
+// Just the length of the body.
+pub struct LengthEvaluator;
+
+impl Evaluator for LengthEvaluator {
+    fn score(&self, _board: &Board, snake: &Battlesnake) -> f32 {
+        snake.body.len() as f32
+    }
+}
+
+// Cells and food reached before the other snakes, plus length. Scores
+// every snake with a single search.
+pub struct VoronoiEvaluator {
+    pub territory_weight: f32,
+    pub food_weight: f32,
+    pub length_weight: f32,
+}
+
+impl Default for VoronoiEvaluator {
+    fn default() -> Self {
+        Self {
+            territory_weight: 4.5,
+            food_weight: 5.0,
+            length_weight: 10.0,
+        }
+    }
+}
+
+impl Evaluator for VoronoiEvaluator {
+    fn score(&self, board: &Board, snake: &Battlesnake) -> f32 {
+        let index = board
+            .snakes
+            .iter()
+            .position(|other| other.id == snake.id)
+            .unwrap();
+        self.scores(board)[index]
+    }
+
+    fn scores(&self, board: &Board) -> Vec<f32> {
+        let territory = voronoi(board);
+        board
+            .snakes
+            .iter()
+            .enumerate()
+            .map(|(index, snake)| {
+                if snake.eliminated_cause.is_some() {
+                    return 0.0;
+                }
+                (territory.cells[index] as f32) * self.territory_weight
+                    + (territory.food[index] as f32) * self.food_weight
+                    + (snake.body.len() as f32) * self.length_weight
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::scenarios::{
+        get_board, get_scenario, made_up_board, FOUR_SNAKES, GET_THE_FOOD,
+    };
+
+    #[test]
+    fn test_fill_prefers_room_and_length() {
+        let board = get_board().board;
+        let evaluator = FillEvaluator::default();
+        // Given time both reach the other 120 cells, lengths are 12 and 6
+        // and count for the fill too. Within three moves they reach 12 and
+        // 17 cells.
+        assert_eq!(evaluator.score(&board, &board.snakes[0]), 726.0);
+        assert_eq!(evaluator.score(&board, &board.snakes[1]), 644.0);
+
+        let shares = evaluator.evaluate(&board);
+        assert!(shares[0] > shares[1]);
+        assert!((shares.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fill_prefers_open_space() {
+        let evaluator = FillEvaluator::default();
+        // A snake alone, standing upright with its head at (x, y).
+        let at = |x, y| {
+            let body = [(x, y), (x, y - 1), (x, y - 2)];
+            let board = made_up_board(11, 11, &[("you", &body)]);
+            let fill = floodfill(&board, "you");
+            (fill, evaluator.score(&board, &board.snakes[0]))
+        };
+        // In the corner or in the middle, the room overall is the same but
+        // less of it is close by.
+        let (corner_fill, corner) = at(0, 2);
+        let (middle_fill, middle) = at(5, 7);
+        assert_eq!(corner_fill, middle_fill);
+        assert!(corner < middle);
+    }
+
+    #[test]
+    fn test_settles_finished_games() {
+        let mut board = get_scenario(FOUR_SNAKES).board;
+        let evaluator = LengthEvaluator;
+        assert_eq!(
+            evaluator.evaluate(&board),
+            [4.0 / 18.0, 6.0 / 18.0, 5.0 / 18.0, 3.0 / 18.0]
+        );
+
+        for snake in &mut board.snakes[1..] {
+            snake.eliminated_cause = Some("test".to_string());
+        }
+        assert_eq!(evaluator.evaluate(&board), [1.0, 0.0, 0.0, 0.0]);
+
+        board.snakes[0].eliminated_cause = Some("test".to_string());
+        assert_eq!(evaluator.evaluate(&board), [0.0; 4]);
+    }
+
+    #[test]
+    fn test_starving_snakes_are_lost() {
+        let mut board = get_scenario(GET_THE_FOOD).board;
+        let evaluator = LengthEvaluator;
+        let fed = evaluator.evaluate(&board);
+
+        // Two turns to spare on the way to the food left at (10, 4).
+        board.food.retain(|food| food.x == 10);
+        board.snakes[0].health = 6;
+        let hungry = evaluator.evaluate(&board);
+        assert!(hungry[0] > 0.0 && hungry[0] < fed[0]);
+
+        board.snakes[0].health = 3;
+        let starving = evaluator.evaluate(&board);
+        assert_eq!(starving[0], 0.0);
+        assert_eq!(starving[1], 1.0);
+
+        // No food at all is no better.
+        board.snakes[0].health = 6;
+        board.food.clear();
+        assert_eq!(evaluator.evaluate(&board)[0], 0.0);
+    }
+
+    #[test]
+    fn test_voronoi_scores_every_snake_in_one_pass() {
+        let board = get_scenario(GET_THE_FOOD).board;
+        let evaluator = VoronoiEvaluator::default();
+        let scores = evaluator.scores(&board);
+        assert_eq!(scores[1], evaluator.score(&board, &board.snakes[1]));
+        assert!(scores.iter().all(|score| *score > 0.0));
+
+        let shares = evaluator.evaluate(&board);
+        assert!((shares.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    }
+}
@@ -2,65 +2,146 @@ use crate::{
     models::{Board, Coord},
     utils,
 };
-use std::{collections::VecDeque, convert::TryInto};
 
-// TODO: Optimize this to floodfill for each snake
-// so we don't have to floodfill multiple times per scoring
+// Counts the cells a snake can reach, taking into account that bodies move
+// out of the way. Segment `i` of a snake of length `len` is gone after
+// `len - i` moves, so its cell is passable once it takes at least that many
+// moves to get there. A stacked tail stays for longer, food eaten on the way
+// is ignored.
 pub fn floodfill(board: &Board, snake_id: &str) -> usize {
-    let mut filled_count = 0;
-    let mut barriers = vec![
-        vec![false; board.width.try_into().unwrap()];
-        board.height.try_into().unwrap()
-    ];
-    let mut filled_tiles = vec![
-        vec![false; board.width.try_into().unwrap()];
-        board.height.try_into().unwrap()
-    ];
-    let mut target_snake = board.snakes.get(0).unwrap();
-    for snake in &board.snakes {
-        if snake.id == snake_id {
-            target_snake = snake;
-        }
-        for coord in &snake.body {
-            barriers[coord.x()][coord.y()] = true
-        }
+    let body = board.get_snake(snake_id).body.len();
+    // Body in the fill to avoid getting punished for getting bigger.
+    floodfill_within(board, snake_id, usize::MAX) + body
+}
+
+// Cells the snake can reach in at most `max_moves` moves. Given time a
+// snake reaches most of an open board, the room close by still tells
+// positions apart.
+pub fn floodfill_within(
+    board: &Board,
+    snake_id: &str,
+    max_moves: usize,
+) -> usize {
+    reach(board, snake_id, max_moves)
+        .into_iter()
+        .filter(|reached| *reached)
+        .count()
+}
+
+// Every cell the snake can reach, indexed by `y * width + x`.
+pub fn reachable_cells(board: &Board, snake_id: &str) -> Vec<bool> {
+    reach(board, snake_id, usize::MAX)
+}
+
+fn reach(board: &Board, snake_id: &str, max_moves: usize) -> Vec<bool> {
+    let width = board.width as usize;
+    let height = board.height as usize;
+    let index = |coord: &Coord| coord.y() * width + coord.x();
+
+    let free_after = moves_until_free(board);
+
+    let mut reached = vec![false; width * height];
+    let start = &board.get_snake(snake_id).head;
+    if !start.in_bounds(board.width(), board.height()) {
+        return reached;
     }
-    let mut q = VecDeque::<Coord>::new();
-    let start_head = target_snake.body.get(0).unwrap();
-    q.push_back(start_head.clone());
-    while !q.is_empty() {
-        let expand_from = q.pop_front().unwrap();
-        for (x, y) in utils::DIRECTIONS {
-            let mut new_explore = Coord::default();
-            new_explore.x = expand_from.x + x;
-            new_explore.y = expand_from.y + y;
-            // If the space is empty, in bounds, and unexplored mark it as accessible
-            // and push it to be explored further from
-            if new_explore.in_bounds(board.width(), board.height())
-                && !barriers[new_explore.x()][new_explore.y()]
-                && !filled_tiles[new_explore.x()][new_explore.y()]
-            {
-                q.push_back(new_explore.clone());
-                filled_tiles[new_explore.x()][new_explore.y()] = true;
-                filled_count += 1;
+    reached[index(start)] = true;
+    let mut frontier = vec![start.clone()];
+    let mut moves = 0;
+    while !frontier.is_empty() && moves < max_moves {
+        moves += 1;
+        let mut next_frontier = vec![];
+        for from in &frontier {
+            for (y, x) in utils::DIRECTIONS {
+                let next = Coord {
+                    x: from.x + x,
+                    y: from.y + y,
+                };
+                if !next.in_bounds(board.width(), board.height()) {
+                    continue;
+                }
+                let cell = index(&next);
+                if reached[cell] || free_after[cell] > moves {
+                    continue;
+                }
+                reached[cell] = true;
+                next_frontier.push(next);
             }
         }
+        frontier = next_frontier;
     }
+    // The head is where the snake is, not where it can go.
+    reached[index(start)] = false;
+    reached
+}
 
-    // Body in the fill to avoid getting punished for getting bigger.
-    filled_count + target_snake.body.len()
+// Moves until each cell is free, 0 for empty cells. Indexed by
+// `y * width + x`.
+pub fn moves_until_free(board: &Board) -> Vec<usize> {
+    let width = board.width as usize;
+    let mut free_after = vec![0; width * board.height as usize];
+    for (_, snake) in board.live_snakes() {
+        let len = snake.body.len();
+        for (i, coord) in snake.body.iter().enumerate() {
+            let cell = coord.y() * width + coord.x();
+            free_after[cell] = free_after[cell].max(len - i);
+        }
+    }
+    free_after
 }
 
 #[cfg(test)]
 mod test {
 
-    use super::floodfill;
-    use crate::test_utils::scenarios::get_board;
+    use super::{floodfill, floodfill_within};
+    use crate::{
+        models::Board,
+        test_utils::scenarios::{get_board, made_up_board},
+    };
 
     #[test]
     fn test_flood_fill() {
         let game_state = get_board().board;
-        assert_eq!(floodfill(&game_state, "long_snake"), 117);
-        assert_eq!(floodfill(&game_state, "short_snake"), 56);
+        // Given time both snakes reach every cell but their own head, on
+        // top of that their bodies count.
+        assert_eq!(floodfill(&game_state, "long_snake"), 120 + 12);
+        assert_eq!(floodfill(&game_state, "short_snake"), 120 + 6);
+        // Close by the long snake is walled in by its own body.
+        let long = floodfill_within(&game_state, "long_snake", 3);
+        let short = floodfill_within(&game_state, "short_snake", 3);
+        assert!(long < short);
+        assert_eq!(floodfill_within(&game_state, "short_snake", 1), 3);
+    }
+
+    // A 3x3 board filled by a snake curled around its head, only the corner
+    // it cannot reach yet is free.
+    fn curled_up(stacked_tail: bool) -> Board {
+        let mut body = vec![
+            (1, 1),
+            (1, 2),
+            (0, 2),
+            (0, 1),
+            (0, 0),
+            (1, 0),
+            (2, 0),
+            (2, 1),
+        ];
+        if stacked_tail {
+            body.push((2, 1));
+        }
+        made_up_board(3, 3, &[("you", &body)])
+    }
+
+    #[test]
+    fn test_fill_follows_the_tail() {
+        let board = curled_up(false);
+        // No free cell is next to the head, but chasing the tail around
+        // reaches every cell.
+        assert_eq!(floodfill(&board, "you"), 8 + 8);
+
+        // Having just eaten the tail stays put for a move too long, only
+        // the body is left.
+        let board = curled_up(true);
+        assert_eq!(floodfill(&board, "you"), 9);
     }
 }
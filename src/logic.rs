@@ -1,8 +1,10 @@
-use log::info;
+use log::{debug, info, log_enabled, Level};
 use serde_json::{json, Value};
 
+use crate::board_graph::BoardGraph;
 use crate::config::Config;
 use crate::models::{Battlesnake, Board, Game};
+use crate::survival::{stall_move, survival, Survival};
 use crate::utils::dir_to_string;
 use crate::{minimax, montecarlo};
 
@@ -23,11 +25,27 @@ pub fn start(_game: &Game, _turn: &u32, _board: &Board, _you: &Battlesnake) {
 }
 
 // end is called when your Battlesnake finishes a game
-pub fn end(_game: &Game, _turn: &u32, _board: &Board, _you: &Battlesnake) {
+pub fn end(game: &Game, _turn: &u32, _board: &Board, you: &Battlesnake) {
     info!("GAME OVER");
+    montecarlo::multitree::forget(&game.id, &you.id);
 }
 
-pub fn get_move(board: &Board, you: &Battlesnake) -> Value {
+pub fn get_move(game: &Game, board: &Board, you: &Battlesnake) -> Value {
+    // Alone in a region there is nobody to search against, just fill it
+    // for as long as possible.
+    if BoardGraph::new(board).is_isolated(board, you) {
+        if let Some(dir) = stall_move(board, &you.id) {
+            // Only worth the search when somebody reads it.
+            if log_enabled!(Level::Debug) {
+                let horizon = (board.width * board.height) as usize;
+                let Survival { turns, exact } =
+                    survival(board, &you.id, horizon);
+                debug!("stalling for {} turns (exact: {})", turns, exact);
+            }
+            return json!({ "move": dir_to_string(dir) });
+        }
+    }
+
     let config = Config::load();
     match config.engine {
         crate::config::Engine::MonteCarlo(config) => {
@@ -35,14 +53,18 @@ pub fn get_move(board: &Board, you: &Battlesnake) -> Value {
                 config,
                 board.clone(),
                 you.clone(),
-            );
-            let tup = tree.get_best_move();
-            json!({ "move": dir_to_string(tup) })
+            )
+            .keep_trees(&game.id);
+            let report = tree.get_best_move();
+            debug!("search report: {}", json!(report));
+            json!({ "move": dir_to_string(report.best_move) })
         }
         crate::config::Engine::MiniMax(config) => {
-            let tree = minimax::Tree::new(config, board.clone(), you.clone());
-            let tup = tree.get_best_move();
-            json!({ "move": dir_to_string(tup) })
+            let mut tree =
+                minimax::Tree::new(config, board.clone(), you.clone());
+            let report = tree.get_best_move();
+            debug!("search report: {}", json!(report));
+            json!({ "move": dir_to_string(report.best_move) })
         }
     }
 }
@@ -9,15 +9,20 @@ use rocket::serde::json::Json;
 use serde_json::Value;
 use std::env;
 
+mod board_graph;
 mod config;
+mod evaluation;
 mod floodfill;
 mod logic;
 mod minimax;
 mod models;
 mod montecarlo;
+mod pathfinding;
 mod simulation;
+mod survival;
 mod test_utils;
 mod utils;
+mod voronoi;
 
 #[get("/")]
 fn handle_index() -> Json<Value> {
@@ -38,7 +43,8 @@ fn handle_start(start_req: Json<GameState>) -> Status {
 
 #[post("/move", format = "json", data = "<move_req>")]
 fn handle_move(move_req: Json<GameState>) -> Json<Value> {
-    let response = logic::get_move(&move_req.board, &move_req.you);
+    let response =
+        logic::get_move(&move_req.game, &move_req.board, &move_req.you);
     Json(response)
 }
 
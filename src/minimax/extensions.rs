@@ -0,0 +1,183 @@
+use std::cell::Cell;
+
+use super::{ordering::distance, Tree};
+use crate::{
+    board_graph::BoardGraph, floodfill::floodfill_within, models::Board,
+};
+
+thread_local! {
+    // Depth of the iteration this thread searches, extensions are capped
+    // relative to it.
+    static ITERATION_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+// Fixed depth search trusts positions that are about to be decided, like a
+// longer head next to ours or a corridor we just walked into. Close to the
+// horizon those lines get searched another turn, in which only the snakes
+// around our head branch so it stays affordable with many snakes.
+impl Tree {
+    // Most turns a line can be searched past the iteration's depth.
+    const MAX_EXTENSIONS: usize = 1;
+    // Heads this close can meet on the next turn.
+    const HEAD_DISTANCE: i32 = 2;
+
+    pub(super) fn start_iteration(max_depth: usize) {
+        ITERATION_DEPTH.with(|iteration_depth| iteration_depth.set(max_depth));
+    }
+
+    // Depth a node at the start of a turn gets searched to. `turn_length`
+    // is the number of plies an extension adds.
+    pub(super) fn extended_depth(
+        &self,
+        board: &Board,
+        depth: usize,
+        max_depth: usize,
+        turn_length: usize,
+    ) -> usize {
+        let limit = ITERATION_DEPTH.with(Cell::get)
+            + Tree::MAX_EXTENSIONS * turn_length;
+        // Extending the root is just a deeper iteration.
+        if depth == 0
+            || depth + turn_length < max_depth
+            || max_depth + turn_length > limit
+            || board.is_terminal()
+        {
+            return max_depth;
+        }
+        // Quiescence: only take the evaluation of quiet positions.
+        let extend = if depth >= max_depth {
+            !self.is_quiet(board)
+        } else {
+            self.in_danger(board)
+                || self.at_a_gap(board, &BoardGraph::new(board))
+        };
+        if extend {
+            max_depth + turn_length
+        } else {
+            max_depth
+        }
+    }
+
+    // No head can collide with ours on the next turn.
+    fn is_quiet(&self, board: &Board) -> bool {
+        let target = &board.snakes[self.target_index()];
+        target.eliminated_cause.is_some()
+            || board.snakes.iter().all(|other| {
+                other.id == target.id
+                    || other.eliminated_cause.is_some()
+                    || distance(&other.head, &target.head) > Tree::HEAD_DISTANCE
+            })
+    }
+
+    // Past the iteration's depth only we and the snakes close to our head
+    // search every move, the others play their best ordered move.
+    pub(super) fn searches_all_moves(
+        &self,
+        board: &Board,
+        mover: usize,
+        depth: usize,
+    ) -> bool {
+        let target = &board.snakes[self.target_index()];
+        depth < ITERATION_DEPTH.with(Cell::get)
+            || board.snakes[mover].id == target.id
+            || distance(&board.snakes[mover].head, &target.head)
+                <= Tree::HEAD_DISTANCE
+    }
+
+    // Our head is near one that wins the collision, or we no longer have
+    // room for our own body. Given time bodies move away and the room gets
+    // big again, so only the moves it takes our body to follow count.
+    fn in_danger(&self, board: &Board) -> bool {
+        let target = &board.snakes[self.target_index()];
+        if target.eliminated_cause.is_some() {
+            return false;
+        }
+        let threatened = board.snakes.iter().any(|other| {
+            other.id != target.id
+                && other.eliminated_cause.is_none()
+                && other.body.len() >= target.body.len()
+                && distance(&other.head, &target.head) <= Tree::HEAD_DISTANCE
+        });
+        let len = target.body.len();
+        threatened || floodfill_within(board, &target.id, len) < len
+    }
+
+    // Another snake can cut us off through a gap, or we can close a gap
+    // behind another snake.
+    fn at_a_gap(&self, board: &Board, graph: &BoardGraph) -> bool {
+        let target = &board.snakes[self.target_index()];
+        target.eliminated_cause.is_none()
+            && (graph.can_be_cut_off(board, target)
+                || !graph.trapping_moves(board, target).is_empty())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::{
+        config::MiniMaxConfig,
+        floodfill::floodfill,
+        test_utils::scenarios::{
+            get_scenario, made_up_board, AVOID_DEATH_ADVANCED, FOUR_SNAKES,
+        },
+    };
+
+    fn tree(scenario: &str) -> Tree {
+        let game_state = get_scenario(scenario);
+        Tree::new(MiniMaxConfig::default(), game_state.board, game_state.you)
+    }
+
+    #[test]
+    fn test_extends_next_to_longer_head() {
+        let tree = tree(FOUR_SNAKES);
+        let board = &tree.root.board_state;
+        assert!(!tree.is_quiet(board));
+        assert!(tree.in_danger(board));
+
+        Tree::start_iteration(8);
+        assert_eq!(tree.extended_depth(board, 4, 8, 4), 12);
+        assert_eq!(tree.extended_depth(board, 8, 8, 4), 12);
+        // Never far from the horizon, past the limit or at the root.
+        assert_eq!(tree.extended_depth(board, 4, 12, 4), 12);
+        assert_eq!(tree.extended_depth(board, 12, 12, 4), 12);
+        assert_eq!(tree.extended_depth(board, 0, 4, 4), 4);
+    }
+
+    #[test]
+    fn test_extends_in_a_dead_end() {
+        // We just went right into a corridor along the bottom edge, under
+        // the body of a long snake that won't move away in time.
+        let mut ceiling = vec![(10, 3), (10, 2)];
+        ceiling.extend((1..=10).rev().map(|x| (x, 1)));
+        let body = [(5, 0), (4, 0), (3, 0), (2, 0), (1, 0), (0, 0)];
+        let board =
+            made_up_board(11, 11, &[("you", &body), ("wall", &ceiling)]);
+        let you = board.snakes[0].clone();
+        let tree = Tree::new(MiniMaxConfig::default(), board, you);
+        let board = &tree.root.board_state;
+        assert!(tree.is_quiet(board));
+        // Five cells to the end of the corridor. The whole fill counts our
+        // body as well, so it never says we are short of room.
+        assert_eq!(floodfill_within(board, "you", body.len()), 5);
+        assert!(floodfill(board, "you") >= body.len());
+        assert!(tree.in_danger(board));
+
+        Tree::start_iteration(4);
+        assert_eq!(tree.extended_depth(board, 2, 4, 2), 6);
+    }
+
+    #[test]
+    fn test_quiet_positions_are_not_extended() {
+        let tree = tree(AVOID_DEATH_ADVANCED);
+        let board = &tree.root.board_state;
+        assert!(tree.is_quiet(board));
+        assert!(!tree.in_danger(board));
+        assert!(!tree.at_a_gap(board, &BoardGraph::new(board)));
+
+        Tree::start_iteration(4);
+        assert_eq!(tree.extended_depth(board, 2, 4, 2), 4);
+        assert_eq!(tree.extended_depth(board, 4, 4, 2), 4);
+    }
+}
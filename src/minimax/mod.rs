@@ -0,0 +1,825 @@
+mod extensions;
+mod multiplayer;
+mod negamax;
+mod ordering;
+mod report;
+mod simultaneous;
+mod transposition;
+
+use log::debug;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::{
+    borrow::Borrow,
+    cell::Cell,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use self::{
+    ordering::MoveOrdering,
+    report::{PlannedMove, RootMove},
+    transposition::{position_hash, Bound, Entry, TranspositionTable},
+};
+use crate::{
+    config::{MiniMaxConfig, SearchStrategy},
+    evaluation::{self, Evaluator},
+    models::{Battlesnake, Board},
+    pathfinding::nearest_food,
+    simulation::Action,
+    utils,
+};
+
+pub use self::report::SearchReport;
+
+thread_local! {
+    // Set while a thread runs one of the Lazy SMP helper searches.
+    static IS_HELPER: Cell<bool> = const { Cell::new(false) };
+}
+
+// Moves as (snake index, direction) pairs, in the order they are played.
+type Line = Vec<(usize, (i32, i32))>;
+// Scores of a node and the line that leads to them.
+type SearchResult = (Vec<f32>, Line);
+
+#[derive(Clone)]
+struct NodeState {
+    board_state: Board,
+}
+
+impl NodeState {
+    // Leaf scores add up to at most this, which shallow pruning relies on.
+    const MAX_SCORE: f32 = 1000.0;
+}
+
+pub struct Tree {
+    snake_map: HashMap<String, usize>,
+    snake_vec: Vec<String>,
+    root: NodeState,
+    target_snake_id: String,
+    max_depth: usize,
+    max_duration: Duration,
+    // Set once the search has started and the time budget runs out.
+    deadline: Option<Instant>,
+    // Set by any node that notices the deadline passed, the iteration
+    // it belongs to is then thrown away.
+    aborted: AtomicBool,
+    transpositions: TranspositionTable,
+    strategy: SearchStrategy,
+    // Lazy SMP: every thread searches the root, sharing work through the
+    // transposition table.
+    pool: ThreadPool,
+    threads: usize,
+    // Tells the helpers the main search finished its iteration.
+    iteration_done: AtomicBool,
+    ordering: MoveOrdering,
+    nodes: AtomicU64,
+    // Scores of every root move in the running iteration.
+    root_moves: Mutex<Vec<RootMove>>,
+    evaluator: Arc<dyn Evaluator>,
+}
+
+impl Tree {
+    pub fn get_next_snake(&self, current_snake: &str) -> &str {
+        let next_index = self.snake_map[current_snake] + 1;
+        return &self.snake_vec[next_index % self.snake_vec.len()];
+    }
+
+    // The last snake still alive finishes the turn, dead snakes never move.
+    fn ends_turn(board: &Board, mover: usize) -> bool {
+        board.snakes[mover + 1..]
+            .iter()
+            .all(|snake| snake.eliminated_cause.is_some())
+    }
+
+    pub fn new(
+        config: MiniMaxConfig,
+        mut starting_board: Board,
+        starting_snake: Battlesnake,
+    ) -> Self {
+        let starting_snake_id = starting_snake.id.clone();
+        utils::fix_snake_order(&mut starting_board, starting_snake);
+        let mut snake_vec = vec![];
+        let mut snake_map = HashMap::new();
+        for (i, snake) in starting_board.borrow().snakes.iter().enumerate() {
+            let copy_snake = &snake.id;
+            snake_vec.push(copy_snake.clone());
+            snake_map.insert(copy_snake.clone(), i);
+        }
+        let ordering = MoveOrdering::new(&starting_board);
+        let root_node_state = NodeState {
+            board_state: starting_board,
+        };
+        return Self {
+            snake_map,
+            snake_vec,
+            root: root_node_state,
+            target_snake_id: starting_snake_id,
+            max_depth: config.depth,
+            max_duration: Duration::from_millis(config.max_duration),
+            deadline: None,
+            aborted: AtomicBool::new(false),
+            transpositions: TranspositionTable::new(config.transposition_size),
+            strategy: config.strategy,
+            pool: ThreadPoolBuilder::new()
+                .num_threads(config.threads)
+                .build()
+                .unwrap(),
+            threads: config.threads.max(1),
+            iteration_done: AtomicBool::new(false),
+            ordering,
+            nodes: AtomicU64::new(0),
+            root_moves: Mutex::new(vec![]),
+            evaluator: evaluation::build(config.evaluator),
+        };
+    }
+
+    pub fn get_best_move(&mut self) -> SearchReport {
+        let start = Instant::now();
+        self.deadline = None;
+        self.aborted.store(false, Ordering::Relaxed);
+        self.nodes.store(0, Ordering::Relaxed);
+
+        let mut best: Option<SearchResult> = None;
+        let mut root_moves = vec![];
+        let mut completed_depth = 0;
+        for max_depth in self.iteration_depths() {
+            // The first iteration always runs to completion so there is a
+            // move to fall back on.
+            if best.is_some() {
+                self.deadline = Some(start + self.max_duration);
+            }
+            let result = self.search_iteration_smp(max_depth, best.as_ref());
+            if self.aborted.load(Ordering::Relaxed) {
+                break;
+            }
+            best = Some(result);
+            root_moves = std::mem::take(self.root_moves.get_mut().unwrap());
+            completed_depth = max_depth;
+            if start.elapsed() >= self.max_duration {
+                break;
+            }
+        }
+        let (scores, line) = best.unwrap();
+
+        let report = SearchReport {
+            best_move: line.first().map(|(_, dir)| *dir).unwrap_or((1, 0)),
+            snakes: self.snake_vec.clone(),
+            scores,
+            principal_variation: line
+                .into_iter()
+                .map(|(index, dir)| PlannedMove {
+                    snake_id: self.snake_vec[index].clone(),
+                    dir,
+                })
+                .collect(),
+            root_moves,
+            depth: completed_depth,
+            nodes: self.nodes.load(Ordering::Relaxed),
+            elapsed: start.elapsed(),
+            food: nearest_food(&self.root.board_state, &self.target_snake_id),
+        };
+        debug!("board state:\n{}", self.root.board_state.to_string());
+        let stats = self.ordering.stats();
+        debug!(
+            "{} cutoffs in {} nodes, {:.1}% on the first move",
+            stats.cutoffs,
+            stats.nodes,
+            100.0 * stats.first_move_cutoffs as f64
+                / stats.cutoffs.max(1) as f64
+        );
+
+        report
+    }
+
+    // Runs one iteration on every thread of the pool. Helpers search the
+    // same root, half of them a turn deeper, and only contribute through
+    // the entries they leave in the table. Their results are dropped once
+    // the main search finishes.
+    fn search_iteration_smp(
+        &self,
+        max_depth: usize,
+        previous: Option<&SearchResult>,
+    ) -> SearchResult {
+        self.iteration_done.store(false, Ordering::Relaxed);
+        self.root_moves.lock().unwrap().clear();
+        self.pool.scope(|s| {
+            for helper in 1..self.threads {
+                s.spawn(move |_| {
+                    IS_HELPER.with(|is_helper| is_helper.set(true));
+                    let depth = max_depth + (helper % 2) * self.turn_length();
+                    self.search_iteration(depth, previous);
+                    IS_HELPER.with(|is_helper| is_helper.set(false));
+                });
+            }
+            let result = self.search_iteration(max_depth, previous);
+            self.iteration_done.store(true, Ordering::Relaxed);
+            result
+        })
+    }
+
+    // Searches the root to a fixed depth with whichever algorithm suits the
+    // number of snakes left.
+    fn search_iteration(
+        &self,
+        max_depth: usize,
+        previous: Option<&SearchResult>,
+    ) -> SearchResult {
+        Tree::start_iteration(max_depth);
+        if self.strategy == SearchStrategy::Simultaneous {
+            return self.search_simultaneous(max_depth);
+        }
+        if let Some(pair) = self.duel_pair() {
+            return self.search_duel(max_depth, pair, previous);
+        }
+        match self.strategy {
+            SearchStrategy::MaxN => self.get_score(
+                0,
+                max_depth,
+                self.root.clone(),
+                vec![NodeState::MAX_SCORE; self.snake_vec.len()],
+                self.target_snake_id.clone(),
+            ),
+            SearchStrategy::Paranoid => self.search_paranoid(max_depth),
+            SearchStrategy::BestReply => self.search_best_reply(max_depth),
+            SearchStrategy::Simultaneous => unreachable!(),
+        }
+    }
+
+    // Depths searched by iterative deepening. Every iteration covers whole
+    // turns so leaves are only scored once all snakes have moved, the last
+    // one is capped at the configured depth.
+    fn iteration_depths(&self) -> Vec<usize> {
+        let turn_length = self.turn_length();
+        if self.strategy == SearchStrategy::Simultaneous {
+            // Searches whole turns only, what is left of the configured
+            // depth would search the last turn again.
+            let turns = (self.max_depth / turn_length).max(1);
+            return (1..=turns).map(|turns| turns * turn_length).collect();
+        }
+        let mut depths: Vec<usize> = (1..)
+            .map(|turns| turns * turn_length)
+            .take_while(|depth| *depth < self.max_depth)
+            .collect();
+        depths.push(self.max_depth);
+        depths
+    }
+
+    fn turn_length(&self) -> usize {
+        match (self.duel_pair(), self.strategy) {
+            // Our move and the single reply make up a turn.
+            (None, SearchStrategy::BestReply) => 2,
+            _ => self.snake_vec.len().max(1),
+        }
+    }
+
+    fn out_of_time(&self) -> bool {
+        if self.aborted.load(Ordering::Relaxed) {
+            return true;
+        }
+        if self.iteration_done.load(Ordering::Relaxed)
+            && IS_HELPER.with(|is_helper| is_helper.get())
+        {
+            return true;
+        }
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                self.aborted.store(true, Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn evaluate(&self, node_state: &NodeState) -> Vec<f32> {
+        self.evaluator
+            .evaluate(&node_state.board_state)
+            .into_iter()
+            .map(|share| share * NodeState::MAX_SCORE)
+            .collect()
+    }
+
+    fn count_node(&self) {
+        self.nodes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Only the main search reports root moves, helpers search other depths.
+    fn record_root_move(&self, depth: usize, dir: (i32, i32), scores: &[f32]) {
+        if depth > 0 || IS_HELPER.with(|is_helper| is_helper.get()) {
+            return;
+        }
+        self.root_moves.lock().unwrap().push(RootMove {
+            dir,
+            scores: scores.to_vec(),
+        });
+    }
+
+    // Stored results are only reused below the root, the root is always
+    // searched so every move gets reported.
+    fn reusable(entry: Option<&Entry>, depth: usize) -> Option<&Entry> {
+        entry.filter(|_| depth > 0)
+    }
+
+    // Reuses a stored result if it was searched at least as deep and is
+    // either exact or already good enough to be pruned.
+    fn transposition_cutoff(
+        entry: Option<&Entry>,
+        remaining_depth: usize,
+        alphas: &[f32],
+        mover: usize,
+    ) -> Option<Vec<f32>> {
+        let entry = entry?;
+        if entry.depth < remaining_depth {
+            return None;
+        }
+        match entry.bound {
+            Bound::Exact => Some(entry.scores.clone()),
+            Bound::Lower if entry.scores[mover] > alphas[mover] => {
+                Some(entry.scores.clone())
+            }
+            Bound::Lower | Bound::Upper => None,
+        }
+    }
+
+    fn evaluate_leaf(
+        &self,
+        node_state: &NodeState,
+        key: u64,
+        depth: usize,
+        max_depth: usize,
+    ) -> Vec<f32> {
+        let scores = self.evaluate(node_state);
+        // Terminal positions are cheap to score, only cache the fills.
+        if depth == max_depth && !self.out_of_time() {
+            self.transpositions.store(Entry {
+                key,
+                depth: 0,
+                scores: scores.clone(),
+                bound: Bound::Exact,
+                best_move: (1, 0),
+            });
+        }
+        scores
+    }
+
+    // Scores are only exact when no ancestor's alpha could cut children
+    // off, otherwise they depend on the path that reached the node.
+    fn store_node(
+        &self,
+        key: u64,
+        remaining_depth: usize,
+        scores: &[f32],
+        (alphas, pruned): (&[f32], bool),
+        best_move: (i32, i32),
+    ) {
+        // A search interrupted by the deadline has unreliable scores.
+        if self.out_of_time() || scores.is_empty() {
+            return;
+        }
+        let bound = if pruned {
+            Bound::Lower
+        } else if alphas.iter().all(|alpha| *alpha >= NodeState::MAX_SCORE) {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.transpositions.store(Entry {
+            key,
+            depth: remaining_depth,
+            scores: scores.to_vec(),
+            bound,
+            best_move,
+        });
+    }
+
+    fn target_index(&self) -> usize {
+        self.snake_map[&self.target_snake_id]
+    }
+
+    // Stores a result of the searches that only track our own score.
+    fn store_bounded(
+        &self,
+        key: u64,
+        remaining_depth: usize,
+        scores: Vec<f32>,
+        (alpha, beta): (f32, f32),
+        best_move: (i32, i32),
+    ) {
+        if self.out_of_time() {
+            return;
+        }
+        let value = scores[self.target_index()];
+        let bound = if value <= alpha {
+            Bound::Upper
+        } else if value >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.transpositions.store(Entry {
+            key,
+            depth: remaining_depth,
+            scores,
+            bound,
+            best_move,
+        });
+    }
+
+    fn bounded_cutoff(
+        &self,
+        entry: Option<&Entry>,
+        remaining_depth: usize,
+        (alpha, beta): (f32, f32),
+    ) -> Option<Vec<f32>> {
+        let entry = entry.filter(|entry| entry.depth >= remaining_depth)?;
+        let value = entry.scores[self.target_index()];
+        let cutoff = match entry.bound {
+            Bound::Exact => true,
+            Bound::Lower => value >= beta,
+            Bound::Upper => value <= alpha,
+        };
+        if cutoff {
+            return Some(entry.scores.clone());
+        }
+        None
+    }
+
+    fn get_score(
+        &self,
+        depth: usize,
+        max_depth: usize,
+        node_state: NodeState,
+        alphas: Vec<f32>,
+        current_snake: String,
+    ) -> SearchResult {
+        self.count_node();
+        let mut best_dir = (1, 0);
+
+        if self.out_of_time() {
+            return (self.evaluate(&node_state), vec![]);
+        }
+
+        let mover = self.snake_map[&current_snake];
+        let max_depth = if mover == self.target_index() {
+            self.extended_depth(
+                &node_state.board_state,
+                depth,
+                max_depth,
+                self.turn_length(),
+            )
+        } else {
+            max_depth
+        };
+        let key = position_hash(&node_state.board_state, mover);
+        let remaining_depth = max_depth - depth;
+        let entry = self.transpositions.probe(key);
+        if let Some(scores) = Tree::transposition_cutoff(
+            Tree::reusable(entry.as_ref(), depth),
+            remaining_depth,
+            &alphas,
+            mover,
+        ) {
+            return (scores, vec![]);
+        }
+
+        if depth == max_depth || node_state.board_state.is_terminal() {
+            let scores = self.evaluate_leaf(&node_state, key, depth, max_depth);
+            return (scores, vec![]);
+        }
+
+        // If eliminated just skip the turn.
+        if node_state
+            .board_state
+            .get_snake(&current_snake)
+            .eliminated_cause
+            .is_some()
+        {
+            return self.get_score(
+                depth,
+                max_depth,
+                node_state,
+                alphas,
+                self.get_next_snake(&current_snake).to_owned(),
+            );
+        }
+
+        let mut new_alphas = alphas.clone();
+        let board_state = &node_state.board_state;
+        let mut max_score = vec![];
+        let mut best_line = vec![];
+        let mut pruned = false;
+
+        let mut moves = self.ordering.order(
+            board_state,
+            mover,
+            depth,
+            entry.map(|entry| entry.best_move),
+        );
+        let all_moves = self.searches_all_moves(board_state, mover, depth);
+        if !all_moves {
+            moves.truncate(1);
+        }
+        let move_count = moves.len();
+        for (index, dir) in moves.into_iter().enumerate() {
+            let mut board_copy = board_state.clone();
+            let action = Action {
+                snake_id: current_snake.to_owned(),
+                dir,
+            };
+            board_copy
+                .execute_action(action, Tree::ends_turn(board_state, mover));
+
+            let new_node = NodeState {
+                board_state: board_copy,
+            };
+            let (new_score, line) = self.get_score(
+                depth + 1,
+                max_depth,
+                new_node,
+                new_alphas.clone(),
+                self.get_next_snake(&current_snake).to_owned(),
+            );
+            self.record_root_move(depth, dir, &new_score);
+
+            if max_score.len() == 0
+                || new_score[self.snake_map[&current_snake]]
+                    > max_score[self.snake_map[&current_snake]]
+            {
+                best_dir = dir;
+                best_line = [vec![(mover, dir)], line].concat();
+                max_score = new_score;
+                for snake_index in 0..new_alphas.len() {
+                    if snake_index == self.snake_map[&current_snake] {
+                        new_alphas[snake_index] =
+                            max_score[self.snake_map[&current_snake]]
+                    } else {
+                        new_alphas[snake_index] = NodeState::MAX_SCORE
+                            - max_score[self.snake_map[&current_snake]]
+                    }
+                }
+            }
+
+            // Perform alpha pruning.
+            // If we found a move better than what is above us we can stop looking.
+            // After the last move there is nothing left to skip.
+            if index + 1 < move_count && max_score[mover] > alphas[mover] {
+                self.ordering.record_cutoff(
+                    board_state,
+                    mover,
+                    depth,
+                    remaining_depth,
+                    best_dir,
+                    index,
+                );
+                pruned = true;
+                break;
+            }
+        }
+        // A single quiescence move says nothing about the whole position.
+        if all_moves {
+            self.store_node(
+                key,
+                remaining_depth,
+                &max_score,
+                (&alphas, pruned),
+                best_dir,
+            );
+        }
+        return (max_score, best_line);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::scenarios::{
+        get_board, get_scenario, AVOID_DEATH_ADVANCED, AVOID_DEATH_GET_FOOD,
+        AVOID_HEAD_TO_HEAD_DEATH, AVOID_SELF_TRAP, DO_NOT_CIRCLE_FOOD,
+        FOUR_SNAKES, GET_THE_FOOD,
+    };
+    use crate::utils::dir_to_string;
+
+    #[test]
+    fn test_avoid_wall() {
+        let game_state = get_board();
+        let mut tree = Tree::new(
+            MiniMaxConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        let best_move = dir_to_string(tree.get_best_move().best_move);
+        assert_ne!("up", best_move)
+    }
+
+    #[test]
+    fn test_avoid_death_get_food() {
+        let game_state = get_scenario(AVOID_DEATH_GET_FOOD);
+        let mut tree = Tree::new(
+            MiniMaxConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        let best_move = dir_to_string(tree.get_best_move().best_move);
+        assert_ne!(best_move, "right")
+    }
+
+    #[test]
+    fn test_avoid_self_trap() {
+        let game_state = get_scenario(AVOID_SELF_TRAP);
+        let mut tree = Tree::new(
+            MiniMaxConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        let best_move = dir_to_string(tree.get_best_move().best_move);
+        assert_ne!(best_move, "up")
+    }
+    #[test]
+    fn test_get_easy_food() {
+        let game_state = get_scenario(GET_THE_FOOD);
+        let mut tree = Tree::new(
+            MiniMaxConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        let best_move = dir_to_string(tree.get_best_move().best_move);
+        assert_eq!(best_move, "down")
+    }
+
+    #[test]
+    fn test_avoid_death_advanced() {
+        let game_state = get_scenario(AVOID_DEATH_ADVANCED);
+        let mut tree = Tree::new(
+            MiniMaxConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        let best_move = dir_to_string(tree.get_best_move().best_move);
+        assert_ne!(best_move, "right")
+    }
+
+    #[test]
+    fn test_do_not_circle_food() {
+        let game_state = get_scenario(DO_NOT_CIRCLE_FOOD);
+        let mut tree = Tree::new(
+            MiniMaxConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        let best_move = dir_to_string(tree.get_best_move().best_move);
+        assert_eq!(best_move, "up")
+    }
+
+    #[test]
+    fn test_parallel_search() {
+        let config = || MiniMaxConfig {
+            threads: 4,
+            ..MiniMaxConfig::default()
+        };
+        let game_state = get_scenario(AVOID_DEATH_ADVANCED);
+        let mut tree = Tree::new(config(), game_state.board, game_state.you);
+        assert_ne!(dir_to_string(tree.get_best_move().best_move), "right");
+
+        let game_state = get_scenario(FOUR_SNAKES);
+        let mut tree = Tree::new(
+            MiniMaxConfig {
+                depth: 8,
+                ..config()
+            },
+            game_state.board,
+            game_state.you,
+        );
+        assert_ne!(dir_to_string(tree.get_best_move().best_move), "up");
+    }
+
+    #[test]
+    fn test_cutoffs_mostly_on_first_move() {
+        let game_state = get_scenario(FOUR_SNAKES);
+        let config = MiniMaxConfig {
+            depth: 8,
+            strategy: SearchStrategy::Paranoid,
+            ..MiniMaxConfig::default()
+        };
+        let mut tree = Tree::new(config, game_state.board, game_state.you);
+        tree.get_best_move();
+        let stats = tree.ordering.stats();
+        assert!(stats.cutoffs > 0);
+        assert!(stats.first_move_cutoffs * 2 > stats.cutoffs);
+    }
+
+    #[test]
+    fn test_respects_time_budget() {
+        let game_state = get_scenario(AVOID_DEATH_ADVANCED);
+        let config = MiniMaxConfig {
+            depth: 100,
+            max_duration: 50,
+            ..MiniMaxConfig::default()
+        };
+        let start = Instant::now();
+        let mut tree = Tree::new(config, game_state.board, game_state.you);
+        let best_move = dir_to_string(tree.get_best_move().best_move);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_ne!(best_move, "right")
+    }
+
+    #[test]
+    fn test_search_report() {
+        let game_state = get_scenario(FOUR_SNAKES);
+        let valid_moves = game_state.board.get_valid_moves(&game_state.you.id);
+        let config = MiniMaxConfig {
+            depth: 8,
+            ..MiniMaxConfig::default()
+        };
+        let mut tree = Tree::new(config, game_state.board, game_state.you);
+        let report = tree.get_best_move();
+
+        assert_eq!(report.depth, 8);
+        assert!(report.nodes > 0);
+        assert_eq!(report.snakes[0], "you");
+        // Max^n never prunes at the root.
+        assert_eq!(report.root_moves.len(), valid_moves.len());
+        let best = report
+            .root_moves
+            .iter()
+            .find(|root_move| root_move.dir == report.best_move)
+            .unwrap();
+        assert_eq!(best.scores, report.scores);
+
+        let variation = &report.principal_variation;
+        assert!(variation.len() > 1);
+        assert_eq!(variation[0].snake_id, "you");
+        assert_eq!(variation[0].dir, report.best_move);
+    }
+
+    #[test]
+    fn test_only_open_windows_store_exact_scores() {
+        let game_state = get_scenario(FOUR_SNAKES);
+        let tree = Tree::new(
+            MiniMaxConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        let key = position_hash(&tree.root.board_state, 0);
+        let search = |alphas: Vec<f32>| {
+            tree.get_score(
+                0,
+                4,
+                tree.root.clone(),
+                alphas,
+                tree.target_snake_id.clone(),
+            );
+            tree.transpositions.probe(key).unwrap().bound
+        };
+        // Our moves are never cut off, but the first one is searched with
+        // the opponents' alphas from above.
+        let mut alphas = vec![0.0; tree.snake_vec.len()];
+        alphas[0] = NodeState::MAX_SCORE;
+        assert_eq!(search(alphas), Bound::Upper);
+        assert_eq!(
+            search(vec![NodeState::MAX_SCORE; tree.snake_vec.len()]),
+            Bound::Exact
+        );
+    }
+
+    #[test]
+    fn test_quiescence_nodes_are_not_stored() {
+        let game_state = get_scenario(FOUR_SNAKES);
+        let tree = Tree::new(
+            MiniMaxConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        let small = tree.snake_vec.iter().position(|id| id == "small");
+        let key = position_hash(&tree.root.board_state, small.unwrap());
+        let search = |depth| {
+            tree.get_score(
+                depth,
+                depth + 1,
+                tree.root.clone(),
+                vec![NodeState::MAX_SCORE; tree.snake_vec.len()],
+                "small".to_string(),
+            );
+            tree.transpositions.probe(key)
+        };
+        // Far from our head, past the iteration only one move is searched.
+        Tree::start_iteration(4);
+        assert!(search(4).is_none());
+        assert!(search(0).is_some());
+    }
+
+    #[test]
+    fn test_avoid_head_to_head_death() {
+        let game_state = get_scenario(AVOID_HEAD_TO_HEAD_DEATH);
+        let mut tree = Tree::new(
+            MiniMaxConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        let best_move = dir_to_string(tree.get_best_move().best_move);
+        assert_ne!(best_move, "left")
+    }
+}
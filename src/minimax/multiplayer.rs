@@ -0,0 +1,428 @@
+use super::{
+    transposition::position_hash, Line, NodeState, SearchResult, Tree,
+};
+use crate::{models::Board, pathfinding::feeding_moves};
+
+// Searches for games with three or more snakes that assume the opponents
+// gang up on us. Both only track our own score so ordinary alpha/beta
+// pruning applies, scores are still passed around as full vectors so they
+// can share the transposition table and reporting with max^n.
+impl Tree {
+    pub(super) fn search_paranoid(&self, max_depth: usize) -> SearchResult {
+        self.paranoid(
+            &self.root,
+            0,
+            max_depth,
+            (-f32::INFINITY, f32::INFINITY),
+            self.target_index(),
+        )
+    }
+
+    // Snakes move in board order, we maximize our score and every other
+    // snake minimizes it.
+    fn paranoid(
+        &self,
+        node_state: &NodeState,
+        depth: usize,
+        max_depth: usize,
+        (mut alpha, mut beta): (f32, f32),
+        mover: usize,
+    ) -> SearchResult {
+        self.count_node();
+        let mut best_dir = (1, 0);
+        if self.out_of_time() {
+            return (self.evaluate(node_state), vec![]);
+        }
+
+        let board = &node_state.board_state;
+        let max_depth = if mover == self.target_index() {
+            self.extended_depth(board, depth, max_depth, self.turn_length())
+        } else {
+            max_depth
+        };
+        let key = position_hash(board, mover);
+        let remaining_depth = max_depth - depth;
+        let entry = self.transpositions.probe(key);
+        if let Some(scores) = self.bounded_cutoff(
+            Tree::reusable(entry.as_ref(), depth),
+            remaining_depth,
+            (alpha, beta),
+        ) {
+            return (scores, vec![]);
+        }
+
+        if depth == max_depth || board.is_terminal() {
+            let scores = self.evaluate_leaf(node_state, key, depth, max_depth);
+            return (scores, vec![]);
+        }
+
+        let next_mover = (mover + 1) % board.snakes.len();
+        // If eliminated just skip the turn.
+        if board.snakes[mover].eliminated_cause.is_some() {
+            return self.paranoid(
+                node_state,
+                depth,
+                max_depth,
+                (alpha, beta),
+                next_mover,
+            );
+        }
+
+        let target = self.target_index();
+        let maximizing = mover == target;
+        let window = (alpha, beta);
+        let snake_id = &self.snake_vec[mover];
+        let mut moves = self.ordering.order(
+            board,
+            mover,
+            depth,
+            entry.map(|entry| entry.best_move),
+        );
+        let all_moves = self.searches_all_moves(board, mover, depth);
+        if !all_moves {
+            moves.truncate(1);
+        }
+        let mut best_scores: Vec<f32> = vec![];
+        let mut best_line = vec![];
+        for (index, dir) in moves.into_iter().enumerate() {
+            let mut board_copy = board.clone();
+            board_copy.execute(snake_id, dir, Tree::ends_turn(board, mover));
+            let child = NodeState {
+                board_state: board_copy,
+            };
+            let (scores, line) = self.paranoid(
+                &child,
+                depth + 1,
+                max_depth,
+                (alpha, beta),
+                next_mover,
+            );
+            self.record_root_move(depth, dir, &scores);
+            let value = scores[target];
+            let improves = best_scores.is_empty()
+                || (maximizing && value > best_scores[target])
+                || (!maximizing && value < best_scores[target]);
+            if improves {
+                best_scores = scores;
+                best_dir = dir;
+                best_line = [vec![(mover, dir)], line].concat();
+            }
+            if maximizing {
+                alpha = alpha.max(value);
+            } else {
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                self.ordering.record_cutoff(
+                    board,
+                    mover,
+                    depth,
+                    remaining_depth,
+                    dir,
+                    index,
+                );
+                break;
+            }
+        }
+
+        // A single quiescence move says nothing about the whole position.
+        if all_moves {
+            self.store_bounded(
+                key,
+                remaining_depth,
+                best_scores.clone(),
+                window,
+                best_dir,
+            );
+        }
+        (best_scores, best_line)
+    }
+
+    pub(super) fn search_best_reply(&self, max_depth: usize) -> SearchResult {
+        self.best_reply(
+            &self.root,
+            0,
+            max_depth,
+            (-f32::INFINITY, f32::INFINITY),
+            true,
+        )
+    }
+
+    // Best-Reply Search. Our layers are followed by a single opponent layer
+    // in which only one opponent gets to choose its move, so the search
+    // reaches far deeper than paranoid at the same cost. The opponents not
+    // searching cannot pass in Battlesnake, they play the move ordering's
+    // first choice instead, as in BRS+.
+    fn best_reply(
+        &self,
+        node_state: &NodeState,
+        depth: usize,
+        max_depth: usize,
+        (mut alpha, mut beta): (f32, f32),
+        our_layer: bool,
+    ) -> SearchResult {
+        self.count_node();
+        let mut best_dir = (1, 0);
+        if self.out_of_time() {
+            return (self.evaluate(node_state), vec![]);
+        }
+
+        let board = &node_state.board_state;
+        let target = self.target_index();
+        let opponents: Vec<usize> = (0..board.snakes.len())
+            .filter(|index| *index != target)
+            .filter(|index| board.snakes[*index].eliminated_cause.is_none())
+            .collect();
+        let key_mover = match opponents.first() {
+            Some(first_opponent) if !our_layer => *first_opponent,
+            _ => target,
+        };
+        let max_depth = if our_layer {
+            self.extended_depth(board, depth, max_depth, self.turn_length())
+        } else {
+            max_depth
+        };
+        let key = position_hash(board, key_mover);
+        let remaining_depth = max_depth - depth;
+        let entry = self.transpositions.probe(key);
+        if let Some(scores) = self.bounded_cutoff(
+            Tree::reusable(entry.as_ref(), depth),
+            remaining_depth,
+            (alpha, beta),
+        ) {
+            return (scores, vec![]);
+        }
+
+        if depth == max_depth || board.is_terminal() {
+            let scores = self.evaluate_leaf(node_state, key, depth, max_depth);
+            return (scores, vec![]);
+        }
+
+        let window = (alpha, beta);
+        let mut best_scores: Vec<f32> = vec![];
+        let mut best_line = vec![];
+        let children = self.best_reply_children(
+            board,
+            &opponents,
+            our_layer,
+            depth,
+            entry.map(|entry| entry.best_move),
+        );
+        for (index, (dir, moves, child)) in children.into_iter().enumerate() {
+            let (scores, line) = self.best_reply(
+                &child,
+                depth + 1,
+                max_depth,
+                (alpha, beta),
+                !our_layer,
+            );
+            self.record_root_move(depth, dir, &scores);
+            let value = scores[target];
+            let improves = best_scores.is_empty()
+                || (our_layer && value > best_scores[target])
+                || (!our_layer && value < best_scores[target]);
+            if improves {
+                best_scores = scores;
+                best_dir = dir;
+                best_line = [moves, line].concat();
+            }
+            if our_layer {
+                alpha = alpha.max(value);
+            } else {
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                // Opponent layer children mix the moves of several snakes,
+                // only our own moves make sense as killers.
+                if our_layer {
+                    self.ordering.record_cutoff(
+                        board,
+                        target,
+                        depth,
+                        remaining_depth,
+                        dir,
+                        index,
+                    );
+                }
+                break;
+            }
+        }
+
+        self.store_bounded(
+            key,
+            remaining_depth,
+            best_scores.clone(),
+            window,
+            best_dir,
+        );
+        (best_scores, best_line)
+    }
+
+    // Our layer moves only us. The opponent layer finishes the turn once for
+    // every move of every opponent, the chosen opponent playing that move.
+    // Children come with the move that picked them and every move played.
+    fn best_reply_children(
+        &self,
+        board: &Board,
+        opponents: &[usize],
+        our_layer: bool,
+        depth: usize,
+        hash_move: Option<(i32, i32)>,
+    ) -> Vec<((i32, i32), Line, NodeState)> {
+        let target = self.target_index();
+        if our_layer {
+            let snake_id = &self.snake_vec[target];
+            return self
+                .ordering
+                .order(board, target, depth, hash_move)
+                .into_iter()
+                .map(|dir| {
+                    let mut board_copy = board.clone();
+                    board_copy.execute(
+                        snake_id,
+                        dir,
+                        Tree::ends_turn(board, target),
+                    );
+                    let child = NodeState {
+                        board_state: board_copy,
+                    };
+                    (dir, vec![(target, dir)], child)
+                })
+                .collect();
+        }
+
+        let default_moves: Vec<(i32, i32)> = opponents
+            .iter()
+            .map(|index| self.ordering.order(board, *index, depth, None)[0])
+            .collect();
+        let mut children = vec![];
+        for (replier, index) in opponents.iter().enumerate() {
+            for dir in feeding_moves(board, &self.snake_vec[*index]) {
+                let mut board_copy = board.clone();
+                let mut moves = vec![];
+                for (opponent, other_index) in opponents.iter().enumerate() {
+                    let opponent_dir = if opponent == replier {
+                        dir
+                    } else {
+                        default_moves[opponent]
+                    };
+                    moves.push((*other_index, opponent_dir));
+                    board_copy.execute(
+                        &self.snake_vec[*other_index],
+                        opponent_dir,
+                        opponent + 1 == opponents.len(),
+                    );
+                }
+                let child = NodeState {
+                    board_state: board_copy,
+                };
+                children.push((dir, moves, child));
+            }
+        }
+        children
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{
+        config::{MiniMaxConfig, SearchStrategy},
+        test_utils::scenarios::{get_scenario, FOUR_SNAKES, MULTI_SNAKE},
+        utils::dir_to_string,
+    };
+
+    const STRATEGIES: [SearchStrategy; 3] = [
+        SearchStrategy::MaxN,
+        SearchStrategy::Paranoid,
+        SearchStrategy::BestReply,
+    ];
+
+    fn config(strategy: SearchStrategy) -> MiniMaxConfig {
+        MiniMaxConfig {
+            depth: 8,
+            strategy,
+            ..MiniMaxConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_can_handle_multiplayer() {
+        for strategy in STRATEGIES {
+            let game_state = get_scenario(MULTI_SNAKE);
+            let mut tree =
+                Tree::new(config(strategy), game_state.board, game_state.you);
+            assert!(!dir_to_string(tree.get_best_move().best_move).is_empty());
+        }
+
+        // Shallow pruning still has to see that up meets a bigger head.
+        let game_state = get_scenario(FOUR_SNAKES);
+        let mut tree = Tree::new(
+            config(SearchStrategy::MaxN),
+            game_state.board,
+            game_state.you,
+        );
+        let report = tree.get_best_move();
+        let score = |dir| {
+            report
+                .root_moves
+                .iter()
+                .find(|root_move| root_move.dir == dir)
+                .unwrap()
+                .scores[0]
+        };
+        assert_ne!(dir_to_string(report.best_move), "up");
+        assert!(score((1, 0)) < score(report.best_move));
+    }
+
+    #[test]
+    fn test_avoid_head_to_head_with_four_snakes() {
+        for strategy in STRATEGIES {
+            let game_state = get_scenario(FOUR_SNAKES);
+            let mut tree =
+                Tree::new(config(strategy), game_state.board, game_state.you);
+            let best_move = dir_to_string(tree.get_best_move().best_move);
+            assert_ne!(
+                best_move, "up",
+                "{:?} walked into a bigger head",
+                strategy
+            );
+            assert_ne!(best_move, "down", "{:?} ran into its neck", strategy);
+        }
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture` to compare
+    // how long each strategy needs for the same depth.
+    #[test]
+    #[ignore]
+    fn compare_strategy_timings() {
+        for depth in [4, 8, 12] {
+            for strategy in STRATEGIES {
+                for threads in [1, 4] {
+                    let game_state = get_scenario(FOUR_SNAKES);
+                    let config = MiniMaxConfig {
+                        depth,
+                        threads,
+                        ..config(strategy)
+                    };
+                    let start = Instant::now();
+                    let mut tree =
+                        Tree::new(config, game_state.board, game_state.you);
+                    let best_move =
+                        dir_to_string(tree.get_best_move().best_move);
+                    println!(
+                        "{:?} depth {} on {} threads chose {} in {:?}",
+                        strategy,
+                        depth,
+                        threads,
+                        best_move,
+                        start.elapsed()
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use super::{
+    transposition::{position_hash, Bound, Entry},
+    Line, NodeState, SearchResult, Tree,
+};
+
+// Two player search used whenever only us and a single opponent are left.
+// The game is treated as zero sum, a position is worth the mover's score
+// minus the opponent's, which lets us use a real alpha/beta window.
+impl Tree {
+    // Half width of the window placed around the previous iteration's value.
+    const ASPIRATION_WINDOW: f32 = 50.0;
+    // Width of the window PVS uses to prove a move is no better than alpha.
+    const NULL_WINDOW: f32 = 0.01;
+
+    // The two snakes still alive, us first, if this is a duel.
+    pub(super) fn duel_pair(&self) -> Option<[usize; 2]> {
+        let alive: Vec<usize> = self
+            .root
+            .board_state
+            .snakes
+            .iter()
+            .enumerate()
+            .filter(|(_, snake)| snake.eliminated_cause.is_none())
+            .map(|(index, _)| index)
+            .collect();
+        match alive[..] {
+            [first, second] => Some([first, second]),
+            _ => None,
+        }
+    }
+
+    pub(super) fn search_duel(
+        &self,
+        max_depth: usize,
+        pair: [usize; 2],
+        previous: Option<&SearchResult>,
+    ) -> SearchResult {
+        let full_window = (-f32::INFINITY, f32::INFINITY);
+        let window = match previous {
+            Some((scores, _)) => (
+                scores[pair[0]] - Tree::ASPIRATION_WINDOW,
+                scores[pair[0]] + Tree::ASPIRATION_WINDOW,
+            ),
+            None => full_window,
+        };
+
+        let mut result =
+            self.negamax(&self.root, 0, max_depth, window, pair, 0);
+        // Fell outside the aspiration window, the value is only a bound.
+        if result.0 <= window.0 || result.0 >= window.1 {
+            self.root_moves.lock().unwrap().clear();
+            result =
+                self.negamax(&self.root, 0, max_depth, full_window, pair, 0);
+        }
+
+        let (value, line) = result;
+        let board = &self.root.board_state;
+        (Tree::duel_scores(board.snakes.len(), pair, 0, value), line)
+    }
+
+    // Spreads a value from the point of view of `pair[turn]` into a score
+    // vector so duel results fit in the transposition table.
+    fn duel_scores(
+        num_snakes: usize,
+        pair: [usize; 2],
+        turn: usize,
+        value: f32,
+    ) -> Vec<f32> {
+        let mut scores = vec![-NodeState::MAX_SCORE; num_snakes];
+        scores[pair[turn]] = value;
+        scores[pair[1 - turn]] = -value;
+        scores
+    }
+
+    // Negamax with principal variation search. `turn` indexes into `pair`,
+    // the second snake of the pair finishes the turn. Moves are ordered by
+    // the table, which at the root holds the previous iteration's choice.
+    fn negamax(
+        &self,
+        node_state: &NodeState,
+        depth: usize,
+        max_depth: usize,
+        (mut alpha, beta): (f32, f32),
+        pair: [usize; 2],
+        turn: usize,
+    ) -> (f32, Line) {
+        self.count_node();
+        let mut best_dir = (1, 0);
+        // The value does not matter, the iteration gets thrown away.
+        if self.out_of_time() {
+            return (0.0, vec![]);
+        }
+
+        let board = &node_state.board_state;
+        let mover = pair[turn];
+        let max_depth = if turn == 0 {
+            self.extended_depth(board, depth, max_depth, pair.len())
+        } else {
+            max_depth
+        };
+        let key = position_hash(board, mover);
+        let remaining_depth = max_depth - depth;
+        let entry = self.transpositions.probe(key);
+        if let Some(entry) = Tree::reusable(entry.as_ref(), depth) {
+            if entry.depth >= remaining_depth {
+                let value = entry.scores[mover];
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => value >= beta,
+                    Bound::Upper => value <= alpha,
+                };
+                if cutoff {
+                    return (value, vec![]);
+                }
+            }
+        }
+
+        if depth == max_depth || board.is_terminal() {
+            let scores = self.evaluate(node_state);
+            let value = scores[mover] - scores[pair[1 - turn]];
+            if depth == max_depth && !self.out_of_time() {
+                self.transpositions.store(Entry {
+                    key,
+                    depth: 0,
+                    scores: Tree::duel_scores(scores.len(), pair, turn, value),
+                    bound: Bound::Exact,
+                    best_move: best_dir,
+                });
+            }
+            return (value, vec![]);
+        }
+
+        let snake_id = &self.snake_vec[mover];
+        let moves = self.ordering.order(
+            board,
+            mover,
+            depth,
+            entry.map(|entry| entry.best_move),
+        );
+        let original_alpha = alpha;
+        let mut best_value = -f32::INFINITY;
+        let mut best_line = vec![];
+        for (index, dir) in moves.into_iter().enumerate() {
+            let mut board_copy = board.clone();
+            board_copy.execute(snake_id, dir, turn == 1);
+            let child = NodeState {
+                board_state: board_copy,
+            };
+            let search = |window: (f32, f32)| {
+                let (value, line) = self.negamax(
+                    &child,
+                    depth + 1,
+                    max_depth,
+                    window,
+                    pair,
+                    1 - turn,
+                );
+                (-value, line)
+            };
+
+            let (value, line) = if index == 0 {
+                search((-beta, -alpha))
+            } else {
+                // Assume the first move was best and only search the rest
+                // fully if they turn out to beat it.
+                let (value, line) =
+                    search((-alpha - Tree::NULL_WINDOW, -alpha));
+                if value > alpha && value < beta {
+                    search((-beta, -alpha))
+                } else {
+                    (value, line)
+                }
+            };
+            let num_snakes = board.snakes.len();
+            self.record_root_move(
+                depth,
+                dir,
+                &Tree::duel_scores(num_snakes, pair, turn, value),
+            );
+
+            if value > best_value {
+                best_value = value;
+                best_dir = dir;
+                best_line = [vec![(mover, dir)], line].concat();
+            }
+            if value > alpha {
+                alpha = value;
+            }
+            if alpha >= beta {
+                self.ordering.record_cutoff(
+                    board,
+                    mover,
+                    depth,
+                    remaining_depth,
+                    dir,
+                    index,
+                );
+                break;
+            }
+        }
+
+        if !self.out_of_time() {
+            let bound = if best_value <= original_alpha {
+                Bound::Upper
+            } else if best_value >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            self.transpositions.store(Entry {
+                key,
+                depth: remaining_depth,
+                scores: Tree::duel_scores(
+                    board.snakes.len(),
+                    pair,
+                    turn,
+                    best_value,
+                ),
+                bound,
+                best_move: best_dir,
+            });
+        }
+        (best_value, best_line)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::{
+        config::MiniMaxConfig,
+        test_utils::scenarios::{get_scenario, AVOID_DEATH_ADVANCED},
+        utils::dir_to_string,
+    };
+
+    #[test]
+    fn test_duel_detected() {
+        let game_state = get_scenario(AVOID_DEATH_ADVANCED);
+        let mut board = game_state.board.clone();
+        let tree =
+            Tree::new(MiniMaxConfig::default(), board.clone(), game_state.you);
+        assert_eq!(tree.duel_pair(), Some([0, 1]));
+
+        board.snakes.push(board.snakes[1].clone());
+        board.snakes[2].id = "third".to_string();
+        let you = board.snakes[0].clone();
+        let tree = Tree::new(MiniMaxConfig::default(), board, you);
+        assert_eq!(tree.duel_pair(), None);
+    }
+
+    #[test]
+    fn test_aspiration_matches_full_window() {
+        let game_state = get_scenario(AVOID_DEATH_ADVANCED);
+        let tree = Tree::new(
+            MiniMaxConfig::default(),
+            game_state.board.clone(),
+            game_state.you.clone(),
+        );
+        let pair = tree.duel_pair().unwrap();
+        let (scores, line) = tree.search_duel(4, pair, None);
+
+        // A badly placed window still has to find the same value.
+        let tree = Tree::new(
+            MiniMaxConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        let guess = (vec![scores[0] + 500.0, 0.0], vec![]);
+        let (windowed_scores, windowed_line) =
+            tree.search_duel(4, pair, Some(&guess));
+        assert_eq!(scores[0], windowed_scores[0]);
+        assert_ne!(dir_to_string(windowed_line[0].1), "right");
+        assert_ne!(dir_to_string(line[0].1), "right");
+    }
+}
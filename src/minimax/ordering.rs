@@ -0,0 +1,270 @@
+use std::{
+    cmp::Reverse,
+    sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering},
+};
+
+use crate::{
+    models::{Board, Coord},
+    pathfinding::{feeding_moves, nearest_food, HUNGRY},
+    utils::DIRECTIONS,
+};
+
+// Decides which moves a node searches first. The stored best move goes
+// first, then the two killer moves of the ply, then moves that caused
+// cutoffs anywhere (history) and finally a cheap look at the board.
+// Everything is atomic so all search threads share what they learn.
+pub struct MoveOrdering {
+    width: usize,
+    height: usize,
+    // Two killer moves per ply as indexes into DIRECTIONS.
+    killers: Vec<[AtomicU8; 2]>,
+    // Indexed by snake, cell and direction.
+    history: Vec<AtomicU32>,
+    nodes: AtomicU64,
+    cutoffs: AtomicU64,
+    first_move_cutoffs: AtomicU64,
+}
+
+#[derive(Debug)]
+pub struct CutoffStats {
+    pub nodes: u64,
+    pub cutoffs: u64,
+    pub first_move_cutoffs: u64,
+}
+
+impl MoveOrdering {
+    const MAX_PLY: usize = 128;
+    const NO_KILLER: u8 = u8::MAX;
+
+    pub fn new(board: &Board) -> Self {
+        let width = board.width as usize;
+        let height = board.height as usize;
+        let history_size =
+            board.snakes.len() * width * height * DIRECTIONS.len();
+        Self {
+            width,
+            height,
+            killers: (0..MoveOrdering::MAX_PLY)
+                .map(|_| {
+                    [
+                        AtomicU8::new(MoveOrdering::NO_KILLER),
+                        AtomicU8::new(MoveOrdering::NO_KILLER),
+                    ]
+                })
+                .collect(),
+            history: (0..history_size).map(|_| AtomicU32::new(0)).collect(),
+            nodes: AtomicU64::new(0),
+            cutoffs: AtomicU64::new(0),
+            first_move_cutoffs: AtomicU64::new(0),
+        }
+    }
+
+    pub fn order(
+        &self,
+        board: &Board,
+        mover: usize,
+        depth: usize,
+        hash_move: Option<(i32, i32)>,
+    ) -> Vec<(i32, i32)> {
+        self.nodes.fetch_add(1, Ordering::Relaxed);
+        let snake = &board.snakes[mover];
+        let food_move = (snake.health <= HUNGRY)
+            .then(|| nearest_food(board, &snake.id))
+            .flatten()
+            .map(|path| path.first_move);
+        let mut moves: Vec<_> = feeding_moves(board, &snake.id)
+            .into_iter()
+            .map(|dir| {
+                let tier = if Some(dir) == hash_move {
+                    3
+                } else if self.is_killer(depth, 0, dir) {
+                    2
+                } else if self.is_killer(depth, 1, dir) {
+                    1
+                } else {
+                    0
+                };
+                let history = self
+                    .history_index(mover, &snake.head, dir)
+                    .map(|index| self.history[index].load(Ordering::Relaxed))
+                    .unwrap_or(0);
+                let score = static_score(board, mover, dir, food_move);
+                ((tier, history, score), dir)
+            })
+            .collect();
+        moves.sort_by_key(|(priority, _)| Reverse(*priority));
+        moves.into_iter().map(|(_, dir)| dir).collect()
+    }
+
+    // Called when `dir` was good enough to stop searching its siblings.
+    pub fn record_cutoff(
+        &self,
+        board: &Board,
+        mover: usize,
+        depth: usize,
+        remaining_depth: usize,
+        dir: (i32, i32),
+        move_index: usize,
+    ) {
+        self.cutoffs.fetch_add(1, Ordering::Relaxed);
+        if move_index == 0 {
+            self.first_move_cutoffs.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if let Some(index) =
+            self.history_index(mover, &board.snakes[mover].head, dir)
+        {
+            let bonus = (remaining_depth * remaining_depth) as u32;
+            self.history[index].fetch_add(bonus, Ordering::Relaxed);
+        }
+
+        let (Some(killers), Some(dir_index)) =
+            (self.killers.get(depth), direction_index(dir))
+        else {
+            return;
+        };
+        if killers[0].load(Ordering::Relaxed) != dir_index {
+            let previous = killers[0].swap(dir_index, Ordering::Relaxed);
+            killers[1].store(previous, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> CutoffStats {
+        CutoffStats {
+            nodes: self.nodes.load(Ordering::Relaxed),
+            cutoffs: self.cutoffs.load(Ordering::Relaxed),
+            first_move_cutoffs: self.first_move_cutoffs.load(Ordering::Relaxed),
+        }
+    }
+
+    fn is_killer(&self, depth: usize, slot: usize, dir: (i32, i32)) -> bool {
+        match (self.killers.get(depth), direction_index(dir)) {
+            (Some(killers), Some(dir_index)) => {
+                killers[slot].load(Ordering::Relaxed) == dir_index
+            }
+            _ => false,
+        }
+    }
+
+    fn history_index(
+        &self,
+        mover: usize,
+        head: &Coord,
+        dir: (i32, i32),
+    ) -> Option<usize> {
+        if !head.in_bounds(self.width as i32, self.height as i32) {
+            return None;
+        }
+        let cell = head.y() * self.width + head.x();
+        let index = (mover * self.width * self.height + cell)
+            * DIRECTIONS.len()
+            + direction_index(dir)? as usize;
+        self.history.get(index).map(|_| index)
+    }
+}
+
+fn direction_index(dir: (i32, i32)) -> Option<u8> {
+    DIRECTIONS
+        .iter()
+        .position(|candidate| *candidate == dir)
+        .map(|index| index as u8)
+}
+
+// Prefers cells with room around them, stays away from heads that would
+// win a collision and takes the first step of the way to food when hungry.
+fn static_score(
+    board: &Board,
+    mover: usize,
+    dir: (i32, i32),
+    food_move: Option<(i32, i32)>,
+) -> i32 {
+    let snake = &board.snakes[mover];
+    let step = |from: &Coord, dir: (i32, i32)| Coord {
+        x: from.x + dir.1,
+        y: from.y + dir.0,
+    };
+    let target = step(&snake.head, dir);
+    let mut score = 0;
+
+    for next_dir in DIRECTIONS {
+        let next = step(&target, next_dir);
+        if next.in_bounds(board.width(), board.height())
+            && !board.intersect_any_snake_body(
+                &next,
+                &snake.id,
+                snake.body.len(),
+            )
+        {
+            score += 1;
+        }
+    }
+
+    for (index, other) in board.snakes.iter().enumerate() {
+        if index == mover
+            || other.eliminated_cause.is_some()
+            || other.body.len() < snake.body.len()
+        {
+            continue;
+        }
+        if distance(&other.head, &target) == 1 {
+            score -= 8;
+        }
+    }
+
+    if food_move == Some(dir) {
+        score += 4;
+    }
+    score
+}
+
+pub(super) fn distance(a: &Coord, b: &Coord) -> i32 {
+    (a.x - b.x).abs() + (a.y - b.y).abs()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::scenarios::{
+        get_scenario, FOUR_SNAKES, GET_THE_FOOD,
+    };
+
+    #[test]
+    fn test_hash_move_then_killers_first() {
+        let board = get_scenario(FOUR_SNAKES).board;
+        let ordering = MoveOrdering::new(&board);
+        let moves = board.get_valid_moves("you");
+        assert_eq!(ordering.order(&board, 0, 3, Some(moves[2]))[0], moves[2]);
+
+        ordering.record_cutoff(&board, 0, 3, 2, moves[1], 0);
+        assert_eq!(ordering.order(&board, 0, 3, None)[0], moves[1]);
+        let ordered = ordering.order(&board, 0, 3, Some(moves[2]));
+        assert_eq!(ordered[..2], [moves[2], moves[1]]);
+        // Killers are per ply, history still prefers the move elsewhere.
+        assert_eq!(ordering.order(&board, 0, 5, None)[0], moves[1]);
+    }
+
+    #[test]
+    fn test_static_order_avoids_bigger_heads() {
+        let board = get_scenario(FOUR_SNAKES).board;
+        let ordering = MoveOrdering::new(&board);
+        let moves = ordering.order(&board, 0, 0, None);
+        // Up puts our head next to the longer snake.
+        assert_eq!(*moves.last().unwrap(), (1, 0));
+        let stats = ordering.stats();
+        assert_eq!(stats.nodes, 1);
+        assert_eq!(stats.cutoffs, 0);
+    }
+
+    #[test]
+    fn test_starving_moves_are_not_searched() {
+        let mut board = get_scenario(GET_THE_FOOD).board;
+        let ordering = MoveOrdering::new(&board);
+        // Left is a move too long to get to the food at (10, 4).
+        board.food.retain(|food| food.x == 10);
+        board.snakes[0].health = 4;
+        let moves = ordering.order(&board, 0, 0, Some((0, -1)));
+        assert_eq!(moves.len(), 2);
+        assert!(!moves.contains(&(0, -1)));
+    }
+}
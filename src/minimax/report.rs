@@ -0,0 +1,79 @@
+use serde::{Serialize, Serializer};
+use std::time::Duration;
+
+use crate::{pathfinding::FoodPath, utils::serialize_dir};
+
+// What the last completed iteration of a search found.
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchReport {
+    #[serde(serialize_with = "serialize_dir")]
+    pub best_move: (i32, i32),
+    // Snake ids in the order used by every score vector.
+    pub snakes: Vec<String>,
+    pub scores: Vec<f32>,
+    // The moves every snake is expected to play, in the order they are
+    // searched. Stops early where the search reused a stored result.
+    pub principal_variation: Vec<PlannedMove>,
+    // Moves pruned at the root only report a bound on their score.
+    pub root_moves: Vec<RootMove>,
+    pub depth: usize,
+    // Nodes visited by every thread, including abandoned iterations.
+    pub nodes: u64,
+    #[serde(serialize_with = "serialize_millis")]
+    pub elapsed: Duration,
+    // Nearest food we can safely get to from the root.
+    pub food: Option<FoodPath>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PlannedMove {
+    pub snake_id: String,
+    #[serde(serialize_with = "serialize_dir")]
+    pub dir: (i32, i32),
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RootMove {
+    #[serde(serialize_with = "serialize_dir")]
+    pub dir: (i32, i32),
+    pub scores: Vec<f32>,
+}
+
+fn serialize_millis<S: Serializer>(
+    elapsed: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(elapsed.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_serializes_directions_as_names() {
+        let report = SearchReport {
+            best_move: (0, -1),
+            snakes: vec!["you".to_string()],
+            scores: vec![1000.0],
+            principal_variation: vec![PlannedMove {
+                snake_id: "you".to_string(),
+                dir: (0, -1),
+            }],
+            root_moves: vec![RootMove {
+                dir: (-1, 0),
+                scores: vec![0.0],
+            }],
+            depth: 3,
+            nodes: 42,
+            elapsed: Duration::from_millis(12),
+            food: None,
+        };
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["best_move"], "left");
+        assert_eq!(json["principal_variation"][0]["dir"], "left");
+        assert_eq!(json["root_moves"][0]["dir"], "down");
+        assert_eq!(json["elapsed"], 12);
+    }
+}
@@ -0,0 +1,270 @@
+use super::{transposition::position_hash, NodeState, SearchResult, Tree};
+use crate::models::Board;
+
+// Every opponent move for one turn, as (snake index, direction) pairs.
+type JointReply = Vec<(usize, (i32, i32))>;
+
+// Searches whole turns at a time. For each of our moves every combination of
+// opponent moves is played against it and the move is worth its worst
+// outcome, so nobody gets to react to a move made in the same turn.
+impl Tree {
+    pub(super) fn search_simultaneous(&self, max_depth: usize) -> SearchResult {
+        // The configured depth counts single snake moves, this counts turns.
+        let turns = (max_depth / self.snake_vec.len()).max(1);
+        Tree::start_iteration(turns);
+        self.simultaneous(&self.root, 0, turns, (-f32::INFINITY, f32::INFINITY))
+    }
+
+    fn simultaneous(
+        &self,
+        node_state: &NodeState,
+        depth: usize,
+        max_depth: usize,
+        (mut alpha, beta): (f32, f32),
+    ) -> SearchResult {
+        self.count_node();
+        let mut best_dir = (1, 0);
+        if self.out_of_time() {
+            return (self.evaluate(node_state), vec![]);
+        }
+
+        let board = &node_state.board_state;
+        let target = self.target_index();
+        // Every node starts a turn.
+        let max_depth = self.extended_depth(board, depth, max_depth, 1);
+        let key = position_hash(board, target);
+        let remaining_depth = max_depth - depth;
+        let entry = self.transpositions.probe(key);
+        if let Some(scores) = self.bounded_cutoff(
+            Tree::reusable(entry.as_ref(), depth),
+            remaining_depth,
+            (alpha, beta),
+        ) {
+            return (scores, vec![]);
+        }
+
+        if depth == max_depth || board.is_terminal() {
+            let scores = self.evaluate_leaf(node_state, key, depth, max_depth);
+            return (scores, vec![]);
+        }
+
+        let window = (alpha, beta);
+        let (replies, all_replies) = self.joint_replies(board, depth);
+        let moves = self.ordering.order(
+            board,
+            target,
+            depth,
+            entry.map(|entry| entry.best_move),
+        );
+        let mut best_scores: Vec<f32> = vec![];
+        let mut best_line = vec![];
+        for (index, dir) in moves.into_iter().enumerate() {
+            // One row of the matrix, our move against every joint reply.
+            let mut worst_scores: Vec<f32> = vec![];
+            let mut worst_line = vec![];
+            for reply in &replies {
+                let child = self.play_turn(board, dir, reply);
+                let worst_so_far = if worst_scores.is_empty() {
+                    beta
+                } else {
+                    worst_scores[target]
+                };
+                let (scores, line) = self.simultaneous(
+                    &child,
+                    depth + 1,
+                    max_depth,
+                    (alpha, worst_so_far.min(beta)),
+                );
+                if worst_scores.is_empty() || scores[target] < worst_so_far {
+                    worst_scores = scores;
+                    worst_line = [reply.clone(), line].concat();
+                }
+                // Already worse than a move we have, no need to look for
+                // an even better reply.
+                if worst_scores[target] <= alpha {
+                    break;
+                }
+            }
+            self.record_root_move(depth, dir, &worst_scores);
+
+            if best_scores.is_empty()
+                || worst_scores[target] > best_scores[target]
+            {
+                best_scores = worst_scores;
+                best_dir = dir;
+                best_line = [vec![(target, dir)], worst_line].concat();
+            }
+            alpha = alpha.max(best_scores[target]);
+            if alpha >= beta {
+                self.ordering.record_cutoff(
+                    board,
+                    target,
+                    depth,
+                    remaining_depth,
+                    dir,
+                    index,
+                );
+                break;
+            }
+        }
+
+        // Replies cut down to one move say nothing about the whole position.
+        if all_replies {
+            self.store_bounded(
+                key,
+                remaining_depth,
+                best_scores.clone(),
+                window,
+                best_dir,
+            );
+        }
+        (best_scores, best_line)
+    }
+
+    // Cartesian product of the moves of every opponent still alive, and
+    // whether all of their moves are in it.
+    fn joint_replies(
+        &self,
+        board: &Board,
+        depth: usize,
+    ) -> (Vec<JointReply>, bool) {
+        let target = self.target_index();
+        let mut replies: Vec<JointReply> = vec![vec![]];
+        let mut all_replies = true;
+        for (index, snake) in board.snakes.iter().enumerate() {
+            if index == target || snake.eliminated_cause.is_some() {
+                continue;
+            }
+            let mut moves = self.ordering.order(board, index, depth, None);
+            if !self.searches_all_moves(board, index, depth) {
+                moves.truncate(1);
+                all_replies = false;
+            }
+            replies = replies
+                .into_iter()
+                .flat_map(|reply| {
+                    moves.iter().map(move |dir| {
+                        let mut reply = reply.clone();
+                        reply.push((index, *dir));
+                        reply
+                    })
+                })
+                .collect();
+        }
+        (replies, all_replies)
+    }
+
+    // Moves everybody before the turn gets resolved, so collisions are
+    // judged on where all the heads end up.
+    fn play_turn(
+        &self,
+        board: &Board,
+        our_move: (i32, i32),
+        reply: &[(usize, (i32, i32))],
+    ) -> NodeState {
+        let mut actions = vec![(self.target_index(), our_move)];
+        actions.extend_from_slice(reply);
+        let mut board_copy = board.clone();
+        for (played, (index, dir)) in actions.iter().enumerate() {
+            board_copy.execute(
+                &self.snake_vec[*index],
+                *dir,
+                played + 1 == actions.len(),
+            );
+        }
+        NodeState {
+            board_state: board_copy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::{
+        config::{MiniMaxConfig, SearchStrategy},
+        test_utils::scenarios::{
+            get_scenario, AVOID_DEATH_GET_FOOD, AVOID_HEAD_TO_HEAD_DEATH,
+            FOUR_SNAKES,
+        },
+        utils::dir_to_string,
+    };
+
+    fn config() -> MiniMaxConfig {
+        MiniMaxConfig {
+            depth: 8,
+            strategy: SearchStrategy::Simultaneous,
+            ..MiniMaxConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_joint_replies_cover_every_combination() {
+        let game_state = get_scenario(FOUR_SNAKES);
+        let board = game_state.board.clone();
+        let tree = Tree::new(config(), game_state.board, game_state.you);
+        let expected: usize = board.snakes[1..]
+            .iter()
+            .map(|snake| board.get_valid_moves(&snake.id).len())
+            .product();
+        Tree::start_iteration(2);
+        let (replies, all_replies) =
+            tree.joint_replies(&tree.root.board_state, 0);
+        assert!(all_replies);
+        assert_eq!(replies.len(), expected);
+        assert!(replies.iter().all(|reply| reply.len() == 3));
+
+        // Past the iteration's depth only the snake next to us branches.
+        let (replies, all_replies) =
+            tree.joint_replies(&tree.root.board_state, 2);
+        assert!(!all_replies);
+        assert_eq!(replies.len(), board.get_valid_moves("big").len());
+        assert!(replies.iter().all(|reply| reply.len() == 3));
+    }
+
+    #[test]
+    fn test_deepens_by_whole_turns() {
+        let game_state = get_scenario(FOUR_SNAKES);
+        let deeper = MiniMaxConfig {
+            depth: 10,
+            ..config()
+        };
+        let tree = Tree::new(deeper, game_state.board, game_state.you);
+        assert_eq!(tree.iteration_depths(), [4, 8]);
+
+        // Less than a turn still searches one.
+        let game_state = get_scenario(FOUR_SNAKES);
+        let shallow = MiniMaxConfig {
+            depth: 2,
+            ..config()
+        };
+        let tree = Tree::new(shallow, game_state.board, game_state.you);
+        assert_eq!(tree.iteration_depths(), [4]);
+    }
+
+    #[test]
+    fn test_avoid_head_to_head_death() {
+        let game_state = get_scenario(AVOID_HEAD_TO_HEAD_DEATH);
+        let mut tree = Tree::new(config(), game_state.board, game_state.you);
+        // Right takes the food next to the longer head.
+        let best_move = dir_to_string(tree.get_best_move().best_move);
+        assert_ne!(best_move, "right")
+    }
+
+    #[test]
+    fn test_avoid_death_get_food() {
+        let game_state = get_scenario(AVOID_DEATH_GET_FOOD);
+        let mut tree = Tree::new(config(), game_state.board, game_state.you);
+        let best_move = dir_to_string(tree.get_best_move().best_move);
+        assert_ne!(best_move, "right")
+    }
+
+    #[test]
+    fn test_avoid_head_to_head_with_four_snakes() {
+        let game_state = get_scenario(FOUR_SNAKES);
+        let mut tree = Tree::new(config(), game_state.board, game_state.you);
+        let best_move = dir_to_string(tree.get_best_move().best_move);
+        assert_ne!(best_move, "up")
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use crate::models::Board;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Bound {
+    // Every move was searched, the scores are the real minimax value.
+    Exact,
+    // The search was cut off, the mover scores at least this much.
+    Lower,
+    // Nothing beat the window, the mover scores at most this much.
+    Upper,
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub key: u64,
+    // Remaining depth the scores were searched to.
+    pub depth: usize,
+    pub scores: Vec<f32>,
+    pub bound: Bound,
+    pub best_move: (i32, i32),
+}
+
+// Fixed size table shared by every search thread. Each slot has its own
+// lock so threads only contend when they hash to the same slot.
+pub struct TranspositionTable {
+    slots: Vec<Mutex<Option<Entry>>>,
+    mask: u64,
+}
+
+impl TranspositionTable {
+    pub fn new(size: usize) -> Self {
+        let size = size.max(1).next_power_of_two();
+        Self {
+            slots: (0..size).map(|_| Mutex::new(None)).collect(),
+            mask: (size - 1) as u64,
+        }
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        let slot = self.slots[(key & self.mask) as usize].lock().unwrap();
+        match slot.as_ref() {
+            Some(entry) if entry.key == key => Some(entry.clone()),
+            _ => None,
+        }
+    }
+
+    // Prefers keeping the deeper result when two positions share a slot.
+    pub fn store(&self, entry: Entry) {
+        let mut slot =
+            self.slots[(entry.key & self.mask) as usize].lock().unwrap();
+        let replace = match slot.as_ref() {
+            Some(existing) => {
+                existing.key != entry.key || existing.depth <= entry.depth
+            }
+            None => true,
+        };
+        if replace {
+            *slot = Some(entry);
+        }
+    }
+}
+
+// Identifies a position independently of the moves that lead to it.
+// The snake to move is part of the key since boards in the middle of a
+// turn look the same no matter who acts next.
+pub fn position_hash(board: &Board, snake_to_move: usize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    snake_to_move.hash(&mut hasher);
+    for snake in &board.snakes {
+        snake.eliminated_cause.is_some().hash(&mut hasher);
+        snake.health.hash(&mut hasher);
+        snake.body.hash(&mut hasher);
+    }
+    board.food.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::scenarios::get_board;
+
+    #[test]
+    fn test_hash_only_depends_on_position() {
+        let board = get_board().board;
+        let mut renamed = board.clone();
+        renamed.snakes[0].name = "someone else".to_string();
+        renamed.snakes[0].latency = "123".to_string();
+        assert_eq!(position_hash(&board, 0), position_hash(&renamed, 0));
+        assert_ne!(position_hash(&board, 0), position_hash(&board, 1));
+
+        let mut moved = board.clone();
+        moved.execute("long_snake", (0, -1), false);
+        assert_ne!(position_hash(&board, 1), position_hash(&moved, 1));
+    }
+
+    #[test]
+    fn test_store_and_probe() {
+        let table = TranspositionTable::new(16);
+        let entry = |key, depth| Entry {
+            key,
+            depth,
+            scores: vec![1.0, 2.0],
+            bound: Bound::Exact,
+            best_move: (0, 1),
+        };
+        table.store(entry(3, 4));
+        assert_eq!(table.probe(3).unwrap().depth, 4);
+        assert!(table.probe(19).is_none());
+        // A shallower result for the same position replaces nothing.
+        table.store(entry(3, 2));
+        assert_eq!(table.probe(3).unwrap().depth, 4);
+        table.store(entry(19, 1));
+        assert_eq!(table.probe(19).unwrap().depth, 1);
+    }
+}
@@ -0,0 +1,245 @@
+use std::sync::atomic::Ordering;
+
+use super::{
+    node_state::{NodeId, NodeState},
+    tree::{Dir, Tree},
+};
+
+// Each snake's pick at a node on the way down, as indexes into its moves.
+type Picks = Vec<usize>;
+
+// Decoupled UCT. Battlesnake turns are simultaneous, so instead of taking
+// turns in the tree every snake picks its move at the same node, from
+// statistics of its own that ignore what the others picked. Children are
+// the positions after each joint move tried so far.
+impl Tree {
+    pub(super) fn decoupled_iteration(&self) {
+        let (path, leaf) = self.select_joint();
+        let (rewards, keys) = self.play_out(leaf);
+        self.back_prop_joint(&path, leaf, &rewards, keys);
+    }
+
+    // Walks down with every snake taking its best move by UCB1, adding
+    // virtual losses to the nodes and moves on the way. Stops at the first
+    // joint move not tried yet, whose child is added, or at the end of the
+    // game.
+    fn select_joint(&self) -> (Vec<(NodeId, Picks)>, NodeId) {
+        let mut path = vec![];
+        let mut id = Tree::ROOT;
+        loop {
+            let (picks, joint, child) = {
+                let nodes = self.nodes.read().unwrap();
+                let node = &nodes[id];
+                node.sims.fetch_add(Tree::VIRTUAL_LOSS, Ordering::Relaxed);
+                if node.snake_moves.is_empty() {
+                    return (path, id);
+                }
+                let parent_sims = node.sims();
+                let picks: Picks = node
+                    .snake_moves
+                    .iter()
+                    .zip(&node.board_state.snakes)
+                    .enumerate()
+                    .map(|(snake, (moves, board_snake))| {
+                        let value = |index: usize| {
+                            let stats = &moves[index];
+                            let key = self.rave.key(
+                                snake,
+                                &board_snake.head,
+                                stats.dir,
+                            );
+                            let rave = self.rave.blend(key, stats.sims());
+                            stats.ucb(parent_sims, rave)
+                        };
+                        let pick = (0..moves.len())
+                            .max_by(|x, y| value(*x).total_cmp(&value(*y)))
+                            .unwrap();
+                        moves[pick]
+                            .sims
+                            .fetch_add(Tree::VIRTUAL_LOSS, Ordering::Relaxed);
+                        pick
+                    })
+                    .collect();
+                let joint: Vec<Dir> = node
+                    .snake_moves
+                    .iter()
+                    .zip(&picks)
+                    .map(|(moves, pick)| moves[*pick].dir)
+                    .collect();
+                let child = node
+                    .joint_moves
+                    .iter()
+                    .position(|known| *known == joint)
+                    .map(|index| node.children[index]);
+                (picks, joint, child)
+            };
+            path.push((id, picks));
+            match child {
+                Some(child) => id = child,
+                None => {
+                    let child = self.add_joint_child(id, joint);
+                    self.nodes.read().unwrap()[child]
+                        .sims
+                        .fetch_add(Tree::VIRTUAL_LOSS, Ordering::Relaxed);
+                    return (path, child);
+                }
+            }
+        }
+    }
+
+    // Plays the joint move, unless another thread already added it.
+    fn add_joint_child(&self, id: NodeId, joint: Vec<Dir>) -> NodeId {
+        let board = {
+            let nodes = self.nodes.read().unwrap();
+            let mut board = nodes[id].board_state.clone();
+            let snake_ids: Vec<String> =
+                board.snakes.iter().map(|snake| snake.id.clone()).collect();
+            for (snake_id, dir) in snake_ids.iter().zip(&joint) {
+                board.execute_dir(snake_id, *dir);
+            }
+            board
+        };
+        let mut nodes = self.nodes.write().unwrap();
+        if let Some(index) = nodes[id]
+            .joint_moves
+            .iter()
+            .position(|known| *known == joint)
+        {
+            return nodes[id].children[index];
+        }
+        let child_id = nodes.len();
+        nodes.push(NodeState::joint(board, joint.clone(), Some(id)));
+        nodes[id].children.push(child_id);
+        nodes[id].joint_moves.push(joint);
+        child_id
+    }
+
+    // Replaces the virtual losses with the result, each snake's share
+    // counting for the move it picked.
+    fn back_prop_joint(
+        &self,
+        path: &[(NodeId, Picks)],
+        leaf: NodeId,
+        rewards: &[f32],
+        mut keys: Vec<usize>,
+    ) {
+        let nodes = self.nodes.read().unwrap();
+        nodes[leaf]
+            .sims
+            .fetch_add(1 - Tree::VIRTUAL_LOSS, Ordering::Relaxed);
+        for (id, picks) in path {
+            let node = &nodes[*id];
+            node.sims
+                .fetch_add(1 - Tree::VIRTUAL_LOSS, Ordering::Relaxed);
+            for (snake, (moves, pick)) in
+                node.snake_moves.iter().zip(picks).enumerate()
+            {
+                let stats = &moves[*pick];
+                stats
+                    .sims
+                    .fetch_add(1 - Tree::VIRTUAL_LOSS, Ordering::Relaxed);
+                stats.wins.fetch_add(rewards[snake] as f64);
+                let head = &node.board_state.snakes[snake].head;
+                keys.extend(self.rave.key(snake, head, stats.dir));
+            }
+        }
+        // Proofs come up from the leaf.
+        for (id, _) in path.iter().rev() {
+            self.prove(&nodes, *id);
+        }
+        if self.rave.enabled() {
+            self.rave.update(keys, rewards);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::{
+        config::{MonteCarloConfig, TreePolicy},
+        test_utils::scenarios::{
+            get_scenario, AVOID_DEATH_GET_FOOD, FOUR_SNAKES, GET_THE_FOOD,
+        },
+        utils::dir_to_string,
+    };
+
+    use super::*;
+
+    fn config() -> MonteCarloConfig {
+        MonteCarloConfig {
+            tree_policy: TreePolicy::Decoupled,
+            max_duration: 300,
+            ..MonteCarloConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_decoupled_can_handle_multiplayer() {
+        let game_state = get_scenario(FOUR_SNAKES);
+        let snakes = game_state.board.snakes.len();
+        let mut tree = Tree::new(config(), game_state.board, game_state.you);
+        tree.get_best_move();
+        let nodes = tree.nodes.get_mut().unwrap();
+        assert_eq!(nodes[Tree::ROOT].snake_moves.len(), snakes);
+    }
+
+    #[test]
+    fn test_decoupled_tree_advances_a_turn() {
+        let game_state = get_scenario(GET_THE_FOOD);
+        let you = game_state.you.clone();
+        let mut tree = Tree::new(config(), game_state.board, game_state.you);
+        tree.get_best_move();
+
+        let nodes = tree.nodes.get_mut().unwrap();
+        let child = *nodes[Tree::ROOT]
+            .children
+            .iter()
+            .max_by_key(|child| nodes[**child].sims.load(Ordering::Relaxed))
+            .unwrap();
+        let sims = nodes[child].sims();
+        let mut board = nodes[child].board_state.clone();
+        board
+            .snakes
+            .retain(|snake| snake.eliminated_cause.is_none());
+        assert!(tree.advance(board, you));
+        assert_eq!(tree.nodes.get_mut().unwrap()[Tree::ROOT].sims(), sims);
+    }
+
+    #[test]
+    fn test_decoupled_avoid_death_get_food() {
+        let game_state = get_scenario(AVOID_DEATH_GET_FOOD);
+        let mut tree = Tree::new(config(), game_state.board, game_state.you);
+        assert_ne!(dir_to_string(tree.get_best_move()), "right");
+    }
+
+    #[test]
+    fn test_every_snake_counts_each_visit_once() {
+        let game_state = get_scenario(GET_THE_FOOD);
+        let you = game_state.you.id.clone();
+        let config = MonteCarloConfig {
+            threads: 3,
+            ..config()
+        };
+        let mut tree = Tree::new(config, game_state.board, game_state.you);
+        tree.get_best_move();
+
+        let nodes = tree.nodes.get_mut().unwrap();
+        assert_eq!(nodes[Tree::ROOT].snake_moves.len(), 2);
+        for (id, node) in nodes.iter().enumerate() {
+            assert_eq!(node.children.len(), node.joint_moves.len());
+            // Nodes below the root get a playout of their own when added.
+            let own = if id == Tree::ROOT { 0.0 } else { 1.0 };
+            for moves in &node.snake_moves {
+                let sims: f64 = moves.iter().map(|stats| stats.sims()).sum();
+                if sims > 0.0 {
+                    assert_eq!(sims + own, node.sims());
+                }
+            }
+        }
+        for child in &nodes[Tree::ROOT].children {
+            assert_eq!(nodes[*child].parent, Some(Tree::ROOT));
+            assert_eq!(nodes[*child].current_snake, you);
+        }
+    }
+}
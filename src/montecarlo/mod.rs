@@ -1,3 +1,9 @@
+mod decoupled;
 pub mod multitree;
 mod node_state;
+mod rave;
+mod reward;
+mod rollout;
+mod selection;
+mod solver;
 mod tree;
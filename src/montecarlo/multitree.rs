@@ -1,22 +1,91 @@
 use rayon::prelude::*;
-use std::collections::HashMap;
-use std::ops::Add;
-use std::sync::mpsc::channel;
+use std::collections::BTreeMap;
+use std::iter;
+use std::sync::Mutex;
 use std::time::Instant;
 
 use crate::{
-    config::MonteCarloConfig,
+    config::{MonteCarloConfig, Parallelism},
     models::{Battlesnake, Board},
     utils,
 };
 
-use super::tree::{Dir, Tree};
+use super::{
+    selection::{self, SearchReport},
+    tree::Tree,
+};
+
+// Trees of each snake's last search, they continue from the position the
+// game reached on its next turn.
+static PREVIOUS_TREES: Mutex<KeptTrees> = Mutex::new(KeptTrees {
+    searches: 0,
+    trees: BTreeMap::new(),
+});
+
+// Drops what was kept for a snake once its game is over.
+pub fn forget(game_id: &str, snake_id: &str) {
+    PREVIOUS_TREES
+        .lock()
+        .unwrap()
+        .trees
+        .remove(&(game_id.to_string(), snake_id.to_string()));
+}
+
+// Game id and snake id, the same snake can play several games at once.
+type Key = (String, String);
+
+struct Kept {
+    // The search they were kept at.
+    kept_at: u64,
+    // Trees keep searching with the config they were built with.
+    config: MonteCarloConfig,
+    trees: Vec<Tree>,
+}
 
+struct KeptTrees {
+    // Searches of every snake so far.
+    searches: u64,
+    trees: BTreeMap<Key, Kept>,
+}
+
+impl KeptTrees {
+    // Games don't always say they are over. Trees whose snake hasn't come
+    // back for this many searches are dropped, enough for a few games at
+    // once while capping how many are kept.
+    const MAX_AGE: u64 = 16;
+
+    // The trees kept for `key`, none if they were built with another
+    // config.
+    fn take(&mut self, key: &Key, config: &MonteCarloConfig) -> Vec<Tree> {
+        match self.trees.remove(key) {
+            Some(kept) if kept.config == *config => kept.trees,
+            _ => vec![],
+        }
+    }
+
+    fn keep(&mut self, key: Key, config: MonteCarloConfig, trees: Vec<Tree>) {
+        self.searches += 1;
+        let searches = self.searches;
+        self.trees
+            .retain(|_, kept| searches - kept.kept_at < KeptTrees::MAX_AGE);
+        let kept = Kept {
+            kept_at: searches,
+            config,
+            trees,
+        };
+        self.trees.insert(key, kept);
+    }
+}
+
+// Runs the configured number of trees, one shared by all threads or one
+// per thread, and adds up their root statistics.
 pub struct Multitree {
     num_trees: usize,
     config: MonteCarloConfig,
     starting_board: Board,
     starting_snake: Battlesnake,
+    // Where the trees are kept between turns, None to start over.
+    kept_as: Option<Key>,
 }
 
 impl Multitree {
@@ -25,41 +94,85 @@ impl Multitree {
         starting_board: Board,
         starting_snake: Battlesnake,
     ) -> Self {
+        let num_trees = match config.parallelism {
+            Parallelism::Root => utils::read_env("NUM_TREES", 10),
+            Parallelism::Tree => 1,
+        };
+        let config = match config.parallelism {
+            Parallelism::Root => MonteCarloConfig {
+                threads: 1,
+                ..config
+            },
+            Parallelism::Tree => config,
+        };
         Self {
-            num_trees: utils::read_env("NUM_TREES", 10),
+            num_trees,
             config,
             starting_board,
             starting_snake,
+            kept_as: None,
+        }
+    }
+
+    // Continues from the trees of the snake's last search in the game, and
+    // keeps the new ones for its next turn.
+    pub fn keep_trees(self, game_id: &str) -> Self {
+        let key = (game_id.to_string(), self.starting_snake.id.clone());
+        Self {
+            kept_as: Some(key),
+            ..self
         }
     }
-    pub fn get_best_move(&mut self) -> (i32, i32) {
+
+    pub fn get_best_move(&mut self) -> SearchReport {
         let start_time = Instant::now();
-        let mut dir_map = HashMap::<Dir, i32>::new();
-        let (sender, reciever) = channel();
-        (0..self.num_trees)
+        let previous = match &self.kept_as {
+            Some(key) => PREVIOUS_TREES.lock().unwrap().take(key, &self.config),
+            None => vec![],
+        };
+        let trees: Vec<Tree> = previous
+            .into_iter()
+            .map(Some)
+            .chain(iter::repeat_with(|| None))
+            .take(self.num_trees)
+            .collect::<Vec<_>>()
             .into_par_iter()
-            .for_each_with(sender, |s, _| {
-                let mut tree = Tree::new(
-                    self.config.clone(),
-                    self.starting_board.clone(),
-                    self.starting_snake.clone(),
-                );
-                tree.get_best_move_with_start_time(start_time.clone());
-                s.send(tree.get_root_scores()).unwrap()
-            });
-        for root_results in reciever.into_iter() {
-            for dir in &root_results {
-                dir_map.insert(
-                    dir.0,
-                    dir_map.get(&dir.0).unwrap_or(&0).add(dir.1),
-                );
-            }
+            .map(|previous| {
+                let mut tree = match previous {
+                    Some(mut tree) => {
+                        tree.advance(
+                            self.starting_board.clone(),
+                            self.starting_snake.clone(),
+                        );
+                        tree
+                    }
+                    None => Tree::new(
+                        self.config.clone(),
+                        self.starting_board.clone(),
+                        self.starting_snake.clone(),
+                    ),
+                };
+                tree.get_best_move_with_start_time(start_time);
+                tree
+            })
+            .collect();
+
+        // Proven moves stay proven when the trees are added up, one tree
+        // proving a win is enough.
+        let moves =
+            selection::merge(trees.iter().map(|tree| tree.get_root_scores()));
+        if let Some(key) = &self.kept_as {
+            PREVIOUS_TREES.lock().unwrap().keep(
+                key.clone(),
+                self.config.clone(),
+                trees,
+            );
+        }
+        SearchReport {
+            best_move: selection::select(self.config.move_selection, &moves)
+                .unwrap_or((1, 0)),
+            moves,
         }
-        dir_map
-            .into_iter()
-            .max_by(|x, y| x.1.cmp(&y.1))
-            .unwrap_or(((1, 0), 0))
-            .0
     }
 }
 
@@ -68,6 +181,7 @@ mod test {
 
     use super::*;
     use crate::{
+        config::MoveSelection,
         test_utils::scenarios::{
             get_board, get_scenario, AVOID_DEATH_ADVANCED,
             AVOID_DEATH_GET_FOOD, AVOID_HEAD_TO_HEAD_DEATH, AVOID_SELF_TRAP,
@@ -84,7 +198,7 @@ mod test {
             game_state.board,
             game_state.you,
         );
-        let best_move = dir_to_string(tree.get_best_move());
+        let best_move = dir_to_string(tree.get_best_move().best_move);
         assert_ne!("up", best_move)
     }
 
@@ -96,7 +210,7 @@ mod test {
             game_state.board,
             game_state.you,
         );
-        let best_move = dir_to_string(tree.get_best_move());
+        let best_move = dir_to_string(tree.get_best_move().best_move);
         assert_ne!(best_move, "right")
     }
 
@@ -108,7 +222,7 @@ mod test {
             game_state.board,
             game_state.you,
         );
-        let best_move = dir_to_string(tree.get_best_move());
+        let best_move = dir_to_string(tree.get_best_move().best_move);
         assert_ne!(best_move, "up")
     }
     #[test]
@@ -119,7 +233,7 @@ mod test {
             game_state.board,
             game_state.you,
         );
-        let best_move = dir_to_string(tree.get_best_move());
+        let best_move = dir_to_string(tree.get_best_move().best_move);
         assert_eq!(best_move, "down")
     }
 
@@ -131,7 +245,7 @@ mod test {
             game_state.board,
             game_state.you,
         );
-        let best_move = dir_to_string(tree.get_best_move());
+        let best_move = dir_to_string(tree.get_best_move().best_move);
         assert_ne!(best_move, "right")
     }
 
@@ -143,7 +257,7 @@ mod test {
             game_state.board.clone(),
             game_state.you,
         );
-        let best_move = dir_to_string(tree.get_best_move());
+        let best_move = dir_to_string(tree.get_best_move().best_move);
         assert_eq!(best_move, "up")
     }
 
@@ -155,7 +269,7 @@ mod test {
             game_state.board,
             game_state.you,
         );
-        let best_move = dir_to_string(tree.get_best_move());
+        let best_move = dir_to_string(tree.get_best_move().best_move);
         assert_ne!(best_move, "right")
     }
 
@@ -167,6 +281,115 @@ mod test {
             game_state.board,
             game_state.you,
         );
-        assert_eq!(dir_to_string(tree.get_best_move()).is_empty(), false);
+        assert_eq!(
+            dir_to_string(tree.get_best_move().best_move).is_empty(),
+            false
+        );
+    }
+
+    #[test]
+    fn test_shared_tree_mode() {
+        let game_state = get_scenario(GET_THE_FOOD);
+        let config = MonteCarloConfig {
+            parallelism: Parallelism::Tree,
+            threads: 2,
+            ..MonteCarloConfig::default()
+        };
+        // One tree searched by both threads, see the tree's own tests.
+        let tree = Multitree::new(config, game_state.board, game_state.you);
+        assert_eq!(tree.num_trees, 1);
+        assert_eq!(tree.config.threads, 2);
+
+        // Root parallelization runs a single threaded tree per thread.
+        let config = MonteCarloConfig {
+            threads: 2,
+            ..MonteCarloConfig::default()
+        };
+        let game_state = get_scenario(GET_THE_FOOD);
+        let tree = Multitree::new(config, game_state.board, game_state.you);
+        assert_eq!(tree.config.threads, 1);
+    }
+
+    #[test]
+    fn test_reports_the_moves_of_every_tree() {
+        let game_state = get_board();
+        let mut tree = Multitree::new(
+            MonteCarloConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        let report = tree.get_best_move();
+        let most_visited = report.moves.iter().max_by_key(|stats| stats.sims);
+        assert_eq!(most_visited.unwrap().dir, report.best_move);
+        for stats in &report.moves {
+            assert!(stats.wins >= 0.0 && stats.wins <= stats.sims as f64);
+        }
+    }
+
+    #[test]
+    fn test_other_move_selections() {
+        for move_selection in [
+            MoveSelection::Max,
+            MoveSelection::Secure,
+            MoveSelection::Weighted,
+        ] {
+            let game_state = get_board();
+            let config = MonteCarloConfig {
+                move_selection,
+                ..MonteCarloConfig::default()
+            };
+            let mut tree =
+                Multitree::new(config, game_state.board, game_state.you);
+            assert_ne!(dir_to_string(tree.get_best_move().best_move), "up");
+        }
+    }
+
+    #[test]
+    fn test_trees_of_snakes_that_left_are_dropped() {
+        let mut kept = KeptTrees {
+            searches: 0,
+            trees: BTreeMap::new(),
+        };
+        let key = |game: &str| (game.to_string(), "you".to_string());
+        let config = MonteCarloConfig::default;
+        kept.keep(key("gone"), config(), vec![]);
+        kept.keep(key("back"), config(), vec![]);
+        for search in 2..KeptTrees::MAX_AGE {
+            kept.keep(key(&format!("other {}", search)), config(), vec![]);
+        }
+        assert!(kept.trees.contains_key(&key("gone")));
+
+        kept.keep(key("back"), config(), vec![]);
+        assert!(!kept.trees.contains_key(&key("gone")));
+        assert!(kept.trees.contains_key(&key("back")));
+        assert_eq!(kept.trees.len() as u64, KeptTrees::MAX_AGE - 1);
+    }
+
+    #[test]
+    fn test_trees_are_only_reused_with_the_same_config() {
+        let game_state = get_scenario(GET_THE_FOOD);
+        let game_id = "test_trees_are_only_reused_with_the_same_config";
+        let key = (game_id.to_string(), game_state.you.id.clone());
+        let search = |config: MonteCarloConfig| {
+            let mut tree = Multitree::new(
+                config,
+                game_state.board.clone(),
+                game_state.you.clone(),
+            )
+            .keep_trees(game_id);
+            tree.get_best_move();
+        };
+        search(MonteCarloConfig::default());
+        let mut kept = PREVIOUS_TREES.lock().unwrap();
+        let trees = kept.take(&key, &MonteCarloConfig::default());
+        assert!(!trees.is_empty());
+        kept.keep(key.clone(), MonteCarloConfig::default(), trees);
+        let other = MonteCarloConfig {
+            move_selection: MoveSelection::Max,
+            ..MonteCarloConfig::default()
+        };
+        assert!(kept.take(&key, &other).is_empty());
+        // Taking them dropped them for good.
+        assert!(kept.take(&key, &MonteCarloConfig::default()).is_empty());
     }
 }
@@ -1,12 +1,21 @@
-use super::tree::{Dir, SnakeTracker};
-use crate::models::Board;
-use std::rc::Rc;
+use serde::{Serialize, Serializer};
+use std::sync::atomic::{AtomicI32, AtomicU64, AtomicUsize, Ordering};
 
-#[derive(Clone)]
+use super::tree::Dir;
+use crate::{
+    evaluation::Evaluator, models::Board, pathfinding::feeding_moves,
+    simulation::EndState, utils::serialize_dir,
+};
+
+// Index of a node in the arena of its tree.
+pub(crate) type NodeId = usize;
+
+#[derive(Serialize)]
 pub(crate) struct NodeState {
-    // Back pointer to parent. Necessary evil.
-    parent: Option<*mut NodeState>,
-    pub(crate) children: Vec<NodeState>,
+    pub(crate) parent: Option<NodeId>,
+    pub(crate) children: Vec<NodeId>,
+    // Boards follow from the moves, dumps leave them out.
+    #[serde(skip)]
     pub(crate) board_state: Board,
 
     // The snake who is about to make a move.
@@ -16,159 +25,212 @@ pub(crate) struct NodeState {
     pub(crate) snake_who_moved: String,
 
     // The direction just moved in.
+    #[serde(serialize_with = "serialize_dir")]
     pub(crate) taken_dir: Dir,
 
-    pub(crate) sims: i32,
-    wins: i32,
+    // Shared by the threads searching the tree. Visits include the virtual
+    // losses of playouts still running below the node. Playouts cut short
+    // count as part of a win.
+    pub(crate) sims: AtomicI32,
+    pub(crate) wins: AtomicF64,
+
+    // Proven by the solver: the snakes that die whatever anyone does from
+    // here, as bits by board index, and the snake that wins for sure.
+    pub(crate) dead: AtomicU64,
+    pub(crate) winner: AtomicUsize,
+
+    // Decoupled nodes only: the moves of every snake with their own
+    // statistics, indexed like the snakes on the board.
+    pub(crate) snake_moves: Vec<Vec<MoveStats>>,
+    // The joint move leading to each child, in the same order.
+    pub(crate) joint_moves: Vec<Vec<Dir>>,
+}
+
+// How one snake's move did at a decoupled node.
+#[derive(Serialize)]
+pub(crate) struct MoveStats {
+    #[serde(serialize_with = "serialize_dir")]
+    pub(crate) dir: Dir,
+    pub(crate) sims: AtomicI32,
+    pub(crate) wins: AtomicF64,
+}
+
+// An f64 threads can add to, stored as its bits.
+pub(crate) struct AtomicF64(AtomicU64);
+
+impl AtomicF64 {
+    pub fn new(value: f64) -> Self {
+        Self(AtomicU64::new(value.to_bits()))
+    }
+
+    pub fn load(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn fetch_add(&self, value: f64) {
+        let _ =
+            self.0
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                    Some((f64::from_bits(bits) + value).to_bits())
+                });
+    }
+}
 
-    // Shared ownership by the nodes.
-    // Too lazy to do ownership stuff for just a helper object.
-    snake_tracker: Rc<SnakeTracker>,
+impl Serialize for AtomicF64 {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.load())
+    }
+}
+
+impl MoveStats {
+    fn new(dir: Dir) -> Self {
+        Self {
+            dir,
+            sims: AtomicI32::new(0),
+            wins: AtomicF64::new(0.0),
+        }
+    }
+
+    pub fn sims(&self) -> f64 {
+        self.sims.load(Ordering::Relaxed) as f64
+    }
+
+    pub fn ucb(&self, parent_sims: f64, rave: Option<(f64, f64)>) -> f64 {
+        if self.sims() == 0.0 {
+            return f64::INFINITY;
+        }
+        let reward = blend(self.wins.load() / self.sims(), rave);
+        reward + ((parent_sims + 1.0).ln() / self.sims()).sqrt() * NodeState::C
+    }
 }
 
 impl NodeState {
     const C: f64 = 1.141;
+    pub const NO_WINNER: usize = usize::MAX;
 
     pub fn new(
         board_state: Board,
         current_snake: String,
-        snake_tracker: Rc<SnakeTracker>,
+        snake_who_moved: String,
+        taken_dir: Dir,
+        parent: Option<NodeId>,
     ) -> Self {
-        let snake_who_moved = snake_tracker.get_prev_snake(&current_snake);
+        let dead = board_state
+            .snakes
+            .iter()
+            .enumerate()
+            .filter(|(_, snake)| snake.eliminated_cause.is_some())
+            .fold(0, |dead, (index, _)| dead | 1 << index);
+        let winner = match board_state.get_endstate() {
+            EndState::Winner(winner) => board_state
+                .snakes
+                .iter()
+                .position(|snake| snake.id == winner),
+            _ => None,
+        };
         NodeState {
-            taken_dir: (1, 0),
-            current_snake,
-            sims: 0,
-            wins: 0,
-            parent: None,
+            parent,
             children: vec![],
             board_state,
-            snake_who_moved: snake_who_moved.to_owned(),
-            snake_tracker: snake_tracker,
+            current_snake,
+            snake_who_moved,
+            taken_dir,
+            sims: AtomicI32::new(0),
+            wins: AtomicF64::new(0.0),
+            dead: AtomicU64::new(dead),
+            winner: AtomicUsize::new(winner.unwrap_or(NodeState::NO_WINNER)),
+            snake_moves: vec![],
+            joint_moves: vec![],
         }
     }
 
-    pub fn new_child(
+    // A decoupled node, at the start of a turn.
+    pub fn joint(
         board_state: Board,
-        current_snake: String,
-        snake_who_moved: String,
-        snake_tracker: Rc<SnakeTracker>,
-        taken_dir: Dir,
+        joint_move: Vec<Dir>,
+        parent: Option<NodeId>,
     ) -> Self {
+        let snake_moves = if board_state.is_terminal() {
+            vec![]
+        } else {
+            board_state
+                .snakes
+                .iter()
+                .map(|snake| {
+                    feeding_moves(&board_state, &snake.id)
+                        .into_iter()
+                        .map(MoveStats::new)
+                        .collect()
+                })
+                .collect()
+        };
+        let first_snake = board_state.snakes[0].id.clone();
+        let last_snake = board_state.snakes.last().unwrap().id.clone();
+        let taken_dir = joint_move.first().copied().unwrap_or((1, 0));
         NodeState {
-            current_snake,
-            sims: 0,
-            wins: 0,
-            taken_dir,
-            snake_who_moved,
-            parent: None,
-            children: vec![],
-            board_state,
-            snake_tracker: snake_tracker,
-        }
-    }
-
-    pub fn expand(&mut self) {
-        if self.board_state.is_terminal() {
-            return;
-        }
-        let mut children = vec![];
-        for dir in self.board_state.get_valid_moves(&self.current_snake) {
-            let mut new_board = self.board_state.clone();
-            new_board.execute_dir(&self.current_snake, dir);
-            let snake_tracker = self.snake_tracker.clone();
-            let next_snake = snake_tracker.get_next_snake(&self.current_snake);
-            let snake_tracker = &self.snake_tracker;
-            children.push(NodeState::new_child(
-                new_board,
-                next_snake.to_string(),
-                self.current_snake.clone(),
-                snake_tracker.clone(),
-                dir,
-            ))
-        }
-        for child in &mut children {
-            child.set_parent(self);
-        }
-        self.children = children;
-    }
-
-    pub fn get_next_snake(&self, snake_id: &str) -> String {
-        return self.snake_tracker.get_next_snake(snake_id).to_string();
-    }
-
-    pub fn set_parent(&mut self, parent: &mut NodeState) {
-        self.parent = Some(parent as *mut NodeState)
-    }
-
-    pub fn play_out(&mut self) {
-        let mut board_copy = self.board_state.clone();
-        let mut end_state = board_copy.get_endstate();
-        let mut current_snake = self.current_snake.clone();
-        while !end_state.is_terminal() {
-            board_copy.execute_random_move(&current_snake);
-            end_state = board_copy.get_endstate();
-            current_snake = self.get_next_snake(&current_snake);
-        }
-        match end_state {
-            crate::simulation::EndState::Winner(winner) => {
-                self.back_prop(&winner)
-            }
-            crate::simulation::EndState::Tie => self.back_prop("tie"),
-            crate::simulation::EndState::Playing => {
-                panic!("somehow the end state ended with playing")
-            }
+            snake_moves,
+            ..NodeState::new(
+                board_state,
+                first_snake,
+                last_snake,
+                taken_dir,
+                parent,
+            )
         }
     }
 
-    pub fn back_prop(&mut self, winner: &str) {
-        if self.snake_who_moved == winner {
-            self.wins += 1;
-        }
-        self.sims += 1;
-        match self.parent {
-            Some(parent) => unsafe {
-                parent.as_mut().unwrap().back_prop(winner)
-            },
-            None => { /* Do nothing */ }
-        }
+    pub fn sims(&self) -> f64 {
+        self.sims.load(Ordering::Relaxed) as f64
     }
 
-    pub fn select_node(&mut self) -> &mut NodeState {
-        if self.children.is_empty() {
-            return self;
+    // Whether the snake at `snake` on the board is proven to win or to
+    // lose from here, None if it isn't proven either way.
+    pub fn proof(&self, snake: usize) -> Option<bool> {
+        let winner = self.winner.load(Ordering::Relaxed);
+        if winner == snake {
+            Some(true)
+        } else if winner != NodeState::NO_WINNER
+            || self.dead.load(Ordering::Relaxed) & 1 << snake != 0
+        {
+            Some(false)
+        } else {
+            None
         }
-        let parent_sims = self.sims();
-        return self
-            .children
-            .iter_mut()
-            .max_by(|x, y| {
-                x.utc_val(parent_sims).total_cmp(&y.utc_val(parent_sims))
-            })
-            .unwrap()
-            .select_node();
-    }
-
-    pub fn sims(&self) -> f64 {
-        return self.sims as f64;
     }
 
     pub fn wins(&self) -> f64 {
-        return self.wins as f64;
+        self.wins.load()
     }
 
-    pub fn utc_val(&self, parent_sims: f64) -> f64 {
-        if self.sims == 0 {
+    // `rave` is the weight and mean of the move's RAVE statistics.
+    pub fn utc_val(
+        &self,
+        parent_sims: f64,
+        evaluator: &dyn Evaluator,
+        rave: Option<(f64, f64)>,
+    ) -> f64 {
+        if self.sims() == 0.0 {
             return f64::INFINITY;
         }
         let discover =
             ((parent_sims + 1.0).ln() / self.sims()).sqrt() * NodeState::C;
-        let reward = self.wins() / self.sims();
-        return reward + discover + self.heuristic();
+        let reward = blend(self.wins() / self.sims(), rave);
+        reward + discover + self.heuristic(evaluator)
     }
 
-    pub fn heuristic(&self) -> f64 {
-        self.board_state.get_snake(&self.snake_who_moved).body.len() as f64
-            / (self.sims() + 1.0)
+    // Bias towards positions the evaluator likes, fading with visits.
+    pub fn heuristic(&self, evaluator: &dyn Evaluator) -> f64 {
+        let snake = self.board_state.get_snake(&self.snake_who_moved);
+        evaluator.score(&self.board_state, snake) as f64 / (self.sims() + 1.0)
+    }
+}
+
+fn blend(reward: f64, rave: Option<(f64, f64)>) -> f64 {
+    match rave {
+        Some((beta, amaf)) => (1.0 - beta) * reward + beta * amaf,
+        None => reward,
     }
 }
@@ -0,0 +1,212 @@
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use crate::{
+    models::{Board, Coord},
+    utils::DIRECTIONS,
+};
+
+use super::{node_state::AtomicF64, tree::Dir};
+
+// All-moves-as-first statistics for a whole tree: how simulations went for
+// a snake that moved in a direction from a cell at any point in them, in
+// the tree or in the playout. With only four moves, the same move from the
+// same cell turns up in many simulations, so these fill up much faster
+// than the statistics of a single node.
+pub(super) struct Rave {
+    width: usize,
+    height: usize,
+    // Visits at which a node's own statistics count as much as these, 0
+    // turns RAVE off.
+    pub equivalence: f64,
+    // Indexed by snake, cell and direction.
+    stats: Vec<RaveStats>,
+}
+
+struct RaveStats {
+    sims: AtomicI32,
+    wins: AtomicF64,
+}
+
+impl Rave {
+    pub fn new(board: &Board, equivalence: f64) -> Self {
+        let (width, height) = (board.width as usize, board.height as usize);
+        let size = if equivalence > 0.0 {
+            board.snakes.len() * width * height * DIRECTIONS.len()
+        } else {
+            0
+        };
+        Self {
+            width,
+            height,
+            equivalence,
+            stats: (0..size)
+                .map(|_| RaveStats {
+                    sims: AtomicI32::new(0),
+                    wins: AtomicF64::new(0.0),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.stats.is_empty()
+    }
+
+    // None for moves that aren't tracked, like dead snakes staying put or
+    // any move with RAVE off.
+    pub fn key(&self, snake: usize, head: &Coord, dir: Dir) -> Option<usize> {
+        if !self.enabled()
+            || !head.in_bounds(self.width as i32, self.height as i32)
+        {
+            return None;
+        }
+        let dir_index = DIRECTIONS.iter().position(|known| *known == dir)?;
+        let cell = head.y() * self.width + head.x();
+        let key = (snake * self.width * self.height + cell) * DIRECTIONS.len()
+            + dir_index;
+        self.stats.get(key).map(|_| key)
+    }
+
+    // How much of a node's value comes from these statistics and their
+    // mean, for a node visited `sims` times. The weight starts at 1 and
+    // fades as the node's own statistics fill up.
+    pub fn blend(&self, key: Option<usize>, sims: f64) -> Option<(f64, f64)> {
+        let stats = &self.stats[key?];
+        let rave_sims = stats.sims.load(Ordering::Relaxed) as f64;
+        if rave_sims == 0.0 {
+            return None;
+        }
+        let beta = (self.equivalence / (3.0 * sims + self.equivalence)).sqrt();
+        Some((beta, stats.wins.load() / rave_sims))
+    }
+
+    // Credits each snake's reward to every move it made in a simulation,
+    // once per move.
+    pub fn update(&self, mut keys: Vec<usize>, rewards: &[f32]) {
+        keys.sort_unstable();
+        keys.dedup();
+        let per_snake = self.width * self.height * DIRECTIONS.len();
+        for key in keys {
+            let stats = &self.stats[key];
+            stats.sims.fetch_add(1, Ordering::Relaxed);
+            stats.wins.fetch_add(rewards[key / per_snake] as f64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{
+        config::{MonteCarloConfig, TreePolicy},
+        montecarlo::tree::Tree,
+        test_utils::scenarios::{
+            get_board, get_scenario, AVOID_DEATH_ADVANCED,
+            AVOID_HEAD_TO_HEAD_DEATH, AVOID_SELF_TRAP, DO_NOT_CIRCLE_FOOD,
+            GET_THE_FOOD,
+        },
+        utils::dir_to_string,
+    };
+
+    fn config() -> MonteCarloConfig {
+        MonteCarloConfig {
+            rave_equivalence: 300.0,
+            ..MonteCarloConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_moves_count_once_per_simulation() {
+        let board = get_scenario(GET_THE_FOOD).board;
+        let rave = Rave::new(&board, 300.0);
+        let head = &board.snakes[1].head;
+        let key = rave.key(1, head, (1, 0));
+        assert_eq!(rave.blend(key, 0.0), None);
+        assert_eq!(rave.key(1, head, (0, 0)), None);
+
+        rave.update(vec![key.unwrap(), key.unwrap()], &[1.0, 0.5]);
+        rave.update(vec![key.unwrap()], &[0.0, 1.0]);
+        assert_eq!(rave.blend(key, 0.0), Some((1.0, 0.75)));
+        // Counts as much as the node's own statistics at the equivalence.
+        let (beta, _) = rave.blend(key, 300.0).unwrap();
+        assert!((beta - 0.5).abs() < 1e-9);
+
+        let plain = Rave::new(&board, 0.0);
+        assert_eq!(plain.key(1, head, (1, 0)), None);
+    }
+
+    #[test]
+    fn test_rave_avoid_wall() {
+        let game_state = get_board();
+        let mut tree = Tree::new(config(), game_state.board, game_state.you);
+        assert_ne!(dir_to_string(tree.get_best_move()), "up");
+    }
+
+    #[test]
+    fn test_rave_avoid_death_advanced() {
+        let game_state = get_scenario(AVOID_DEATH_ADVANCED);
+        let mut tree = Tree::new(config(), game_state.board, game_state.you);
+        assert_ne!(dir_to_string(tree.get_best_move()), "right");
+    }
+
+    #[test]
+    fn test_rave_avoid_head_to_head_death() {
+        let game_state = get_scenario(AVOID_HEAD_TO_HEAD_DEATH);
+        let config = MonteCarloConfig {
+            tree_policy: TreePolicy::Decoupled,
+            ..config()
+        };
+        let mut tree = Tree::new(config, game_state.board, game_state.you);
+        assert_ne!(dir_to_string(tree.get_best_move()), "right");
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture` to see how
+    // often plain UCT and RAVE find the right move in the time they get.
+    #[test]
+    #[ignore]
+    fn compare_rave_with_plain_uct() {
+        const SEARCHES: usize = 20;
+        // The move to make, or to avoid.
+        let scenarios = [
+            (GET_THE_FOOD, "down", true),
+            (DO_NOT_CIRCLE_FOOD, "up", true),
+            (AVOID_SELF_TRAP, "up", false),
+            (AVOID_HEAD_TO_HEAD_DEATH, "right", false),
+        ];
+        for (scenario, dir, make) in scenarios {
+            for equivalence in [0.0, 100.0, 300.0, 1000.0] {
+                for max_duration in [50, 200] {
+                    let start = Instant::now();
+                    let right = (0..SEARCHES)
+                        .filter(|_| {
+                            let game_state = get_scenario(scenario);
+                            let config = MonteCarloConfig {
+                                rave_equivalence: equivalence,
+                                max_duration,
+                                ..MonteCarloConfig::default()
+                            };
+                            let mut tree = Tree::new(
+                                config,
+                                game_state.board,
+                                game_state.you,
+                            );
+                            (dir_to_string(tree.get_best_move()) == dir) == make
+                        })
+                        .count();
+                    println!(
+                        "{} equivalence {} in {}ms: right {} of {} in {:?}",
+                        scenario,
+                        equivalence,
+                        max_duration,
+                        right,
+                        SEARCHES,
+                        start.elapsed()
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// Every snake's reward for where it finished a playout, from 1 for first
+// place down to 0 for last. `died_at` is the turn of the playout each
+// snake died in, snakes dying in the same turn share their places. Snakes
+// still alive when the playout was cut short are placed between the best
+// and worst of the places left by their share of the board.
+pub fn placement_rewards(
+    died_at: &[Option<usize>],
+    shares: &[f32],
+) -> Vec<f32> {
+    let snakes = died_at.len();
+    if snakes < 2 {
+        return vec![1.0; snakes];
+    }
+    let place_reward =
+        |place: usize| (snakes - 1 - place) as f32 / (snakes - 1) as f32;
+    // Survivors first, then the snakes that lasted longest.
+    let mut order: Vec<usize> = (0..snakes).collect();
+    order.sort_by_key(|index| {
+        std::cmp::Reverse(died_at[*index].unwrap_or(usize::MAX))
+    });
+
+    let mut rewards = vec![0.0; snakes];
+    let mut start = 0;
+    while start < snakes {
+        let died = died_at[order[start]];
+        let end = start
+            + order[start..]
+                .iter()
+                .take_while(|index| died_at[**index] == died)
+                .count();
+        let group = &order[start..end];
+        let (best, worst) = (place_reward(start), place_reward(end - 1));
+        let total: f32 = group.iter().map(|index| shares[*index]).sum();
+        for index in group {
+            rewards[*index] = if died.is_none() && total > 0.0 {
+                worst + (best - worst) * shares[*index] / total
+            } else {
+                (start..end).map(place_reward).sum::<f32>() / group.len() as f32
+            };
+        }
+        start = end;
+    }
+    rewards
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_winner_takes_first_place() {
+        assert_eq!(
+            placement_rewards(&[None, Some(5)], &[1.0, 0.0]),
+            [1.0, 0.0]
+        );
+        assert_eq!(placement_rewards(&[Some(5), Some(5)], &[0.0; 2]), [0.5; 2]);
+        assert_eq!(placement_rewards(&[None], &[1.0]), [1.0]);
+    }
+
+    #[test]
+    fn test_surviving_longer_is_worth_more() {
+        let rewards =
+            placement_rewards(&[Some(3), None, Some(7), Some(3)], &[0.0; 4]);
+        let third = 1.0 / 3.0;
+        assert_eq!(rewards, [third / 2.0, 1.0, 2.0 / 3.0, third / 2.0]);
+    }
+
+    #[test]
+    fn test_survivors_split_by_their_share() {
+        let rewards = placement_rewards(&[None, None], &[0.75, 0.25]);
+        assert_eq!(rewards, [0.75, 0.25]);
+        let rewards =
+            placement_rewards(&[None, None, Some(2)], &[0.75, 0.25, 0.0]);
+        assert_eq!(rewards, [0.875, 0.625, 0.0]);
+        // Starving snakes have no share, the places are split evenly.
+        let rewards = placement_rewards(&[None, None, Some(2)], &[0.0; 3]);
+        assert_eq!(rewards, [0.75, 0.75, 0.0]);
+    }
+}
@@ -0,0 +1,240 @@
+use rand::{seq::SliceRandom, Rng};
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::{
+    config::RolloutKind,
+    floodfill::moves_until_free,
+    models::{Board, Coord},
+    pathfinding::HUNGRY,
+    utils,
+};
+
+use super::tree::Dir;
+
+// Picks the moves of playouts.
+pub trait RolloutPolicy: Send + Sync {
+    fn choose(&self, board: &Board, snake_id: &str) -> Dir;
+
+    fn play(&self, board: &mut Board, snake_id: &str) -> Dir {
+        let dir = self.choose(board, snake_id);
+        board.execute_dir(snake_id, dir);
+        dir
+    }
+}
+
+pub fn build(kind: RolloutKind, epsilon: f64) -> Arc<dyn RolloutPolicy> {
+    match kind {
+        RolloutKind::Random => Arc::new(RandomRollout),
+        RolloutKind::Heuristic => Arc::new(HeuristicRollout {
+            epsilon: epsilon.clamp(0.0, 1.0),
+        }),
+    }
+}
+
+pub struct RandomRollout;
+
+impl RolloutPolicy for RandomRollout {
+    fn choose(&self, board: &Board, snake_id: &str) -> Dir {
+        *board
+            .get_valid_moves(snake_id)
+            .choose(&mut rand::thread_rng())
+            .unwrap()
+    }
+}
+
+// Slower than random moves, but games play out more like real ones. With
+// probability `epsilon` a random move is taken instead.
+pub struct HeuristicRollout {
+    pub epsilon: f64,
+}
+
+impl RolloutPolicy for HeuristicRollout {
+    // Moves into room for the whole body come first, then moves away from
+    // heads that win a collision, then moves towards food when hungry.
+    // Ties are broken at random.
+    fn choose(&self, board: &Board, snake_id: &str) -> Dir {
+        let mut rng = rand::thread_rng();
+        let moves = board.get_valid_moves(snake_id);
+        if moves.len() == 1 || rng.gen_bool(self.epsilon) {
+            return *moves.choose(&mut rng).unwrap();
+        }
+        let snake = board.get_snake(snake_id);
+        let free_after = moves_until_free(board);
+        let scored: Vec<_> = moves
+            .into_iter()
+            .map(|dir| {
+                let target = Coord {
+                    x: snake.head.x + dir.1,
+                    y: snake.head.y + dir.0,
+                };
+                let length = snake.body.len();
+                let roomy = room(board, &free_after, &target, length) >= length;
+                let safe = !board.snakes.iter().any(|other| {
+                    other.id != snake.id
+                        && other.eliminated_cause.is_none()
+                        && other.body.len() >= length
+                        && distance(&other.head, &target) == 1
+                });
+                let food = if snake.health <= HUNGRY {
+                    board
+                        .food
+                        .iter()
+                        .map(|food| -distance(food, &target))
+                        .max()
+                        .unwrap_or(0)
+                } else {
+                    0
+                };
+                ((roomy, safe, food), dir)
+            })
+            .collect();
+        let best = scored.iter().map(|(score, _)| *score).max().unwrap();
+        let best_moves: Vec<Dir> = scored
+            .into_iter()
+            .filter(|(score, _)| *score == best)
+            .map(|(_, dir)| dir)
+            .collect();
+        *best_moves.choose(&mut rng).unwrap()
+    }
+}
+
+// Cells reachable from `start`, counting no further than `limit`.
+fn room(
+    board: &Board,
+    free_after: &[usize],
+    start: &Coord,
+    limit: usize,
+) -> usize {
+    let (width, height) = (board.width(), board.height());
+    if !start.in_bounds(width, height) {
+        return 0;
+    }
+    let index = |coord: &Coord| coord.y() * width as usize + coord.x();
+    let mut reached = vec![false; free_after.len()];
+    reached[index(start)] = true;
+    let mut count = 1;
+    let mut pending = VecDeque::from([(start.clone(), 1)]);
+    while let Some((from, moves)) = pending.pop_front() {
+        for (y, x) in utils::DIRECTIONS {
+            if count >= limit {
+                return count;
+            }
+            let next = Coord {
+                x: from.x + x,
+                y: from.y + y,
+            };
+            if !next.in_bounds(width, height) {
+                continue;
+            }
+            let cell = index(&next);
+            if reached[cell] || free_after[cell] > moves + 1 {
+                continue;
+            }
+            reached[cell] = true;
+            count += 1;
+            pending.push_back((next, moves + 1));
+        }
+    }
+    count
+}
+
+fn distance(a: &Coord, b: &Coord) -> i32 {
+    (a.x - b.x).abs() + (a.y - b.y).abs()
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{
+        simulation::EndState,
+        test_utils::scenarios::{
+            get_scenario, AVOID_SELF_TRAP, FOUR_SNAKES, GET_THE_FOOD,
+        },
+    };
+
+    fn heuristic() -> HeuristicRollout {
+        HeuristicRollout { epsilon: 0.0 }
+    }
+
+    #[test]
+    fn test_stays_out_of_dead_ends() {
+        let mut game_state = get_scenario(AVOID_SELF_TRAP);
+        // Long enough that up, into the corner, is a dead end.
+        let snake = &mut game_state.board.snakes[0];
+        for x in (5..8).rev() {
+            snake.body.push(Coord { x, y: 10 });
+        }
+        for _ in 0..20 {
+            let dir = heuristic().choose(&game_state.board, &game_state.you.id);
+            assert_ne!(dir, (1, 0));
+        }
+    }
+
+    #[test]
+    fn test_stays_away_from_bigger_heads() {
+        let game_state = get_scenario(FOUR_SNAKES);
+        for _ in 0..20 {
+            let dir = heuristic().choose(&game_state.board, &game_state.you.id);
+            assert_ne!(dir, (1, 0));
+        }
+    }
+
+    #[test]
+    fn test_hungry_snakes_go_for_food() {
+        let mut game_state = get_scenario(GET_THE_FOOD);
+        game_state.board.snakes[0].health = 5;
+        let dir = heuristic().choose(&game_state.board, &game_state.you.id);
+        assert_eq!(dir, (-1, 0));
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture` to compare
+    // how fast each policy plays out games and how long our snake lasts.
+    #[test]
+    #[ignore]
+    fn compare_rollout_policies() {
+        const PLAYOUTS: usize = 2000;
+        let policies = [
+            ("random", build(RolloutKind::Random, 0.0)),
+            ("heuristic", build(RolloutKind::Heuristic, 0.0)),
+            ("epsilon 0.2", build(RolloutKind::Heuristic, 0.2)),
+        ];
+        for scenario in [GET_THE_FOOD, FOUR_SNAKES, AVOID_SELF_TRAP] {
+            let game_state = get_scenario(scenario);
+            let mut board = game_state.board.clone();
+            utils::fix_snake_order(&mut board, game_state.you.clone());
+            for (name, policy) in &policies {
+                let start = Instant::now();
+                let (mut wins, mut turns) = (0, 0);
+                for _ in 0..PLAYOUTS {
+                    let mut board = board.clone();
+                    let ids: Vec<String> =
+                        board.snakes.iter().map(|s| s.id.clone()).collect();
+                    let mut mover = 0;
+                    while !board.is_terminal() {
+                        policy.play(&mut board, &ids[mover]);
+                        mover = (mover + 1) % ids.len();
+                        if mover == 0 {
+                            turns += 1;
+                        }
+                    }
+                    if board.get_endstate() == EndState::Winner(ids[0].clone())
+                    {
+                        wins += 1;
+                    }
+                }
+                println!(
+                    "{} {}: won {} of {}, {:.1} turns per game, {:.0} playouts/s",
+                    scenario,
+                    name,
+                    wins,
+                    PLAYOUTS,
+                    turns as f64 / PLAYOUTS as f64,
+                    PLAYOUTS as f64 / start.elapsed().as_secs_f64()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{config::MoveSelection, utils::serialize_dir};
+
+use super::tree::Dir;
+
+// How the search went for one of our moves at the root.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RootStats {
+    #[serde(serialize_with = "serialize_dir")]
+    pub dir: Dir,
+    pub sims: i32,
+    pub wins: f64,
+    // Whether the move is proven to win or to lose.
+    pub proven: Option<bool>,
+}
+
+impl RootStats {
+    // Weight of the uncertainty in the lower confidence bound.
+    const CONFIDENCE: f64 = 1.0;
+
+    pub fn win_rate(&self) -> f64 {
+        if self.sims == 0 {
+            return 0.0;
+        }
+        self.wins / self.sims as f64
+    }
+
+    fn lower_bound(&self) -> f64 {
+        if self.sims == 0 {
+            return f64::NEG_INFINITY;
+        }
+        self.win_rate() - RootStats::CONFIDENCE / (self.sims as f64).sqrt()
+    }
+
+    fn score(&self, selection: MoveSelection) -> f64 {
+        match selection {
+            MoveSelection::Robust => self.sims as f64,
+            MoveSelection::Max => self.win_rate(),
+            MoveSelection::Secure => self.lower_bound(),
+            MoveSelection::Weighted => self.wins,
+        }
+    }
+}
+
+// What a search found, summed over all its trees.
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchReport {
+    #[serde(serialize_with = "serialize_dir")]
+    pub best_move: Dir,
+    pub moves: Vec<RootStats>,
+}
+
+// Adds up the root statistics of several trees. A move one tree proved
+// stays proven.
+pub fn merge(
+    trees: impl IntoIterator<Item = Vec<RootStats>>,
+) -> Vec<RootStats> {
+    let mut merged = BTreeMap::<Dir, RootStats>::new();
+    for stats in trees.into_iter().flatten() {
+        match merged.get_mut(&stats.dir) {
+            Some(total) => {
+                total.sims += stats.sims;
+                total.wins += stats.wins;
+                total.proven = total.proven.or(stats.proven);
+            }
+            None => {
+                merged.insert(stats.dir, stats);
+            }
+        }
+    }
+    merged.into_values().collect()
+}
+
+// A proven win is taken right away and proven losses only when nothing
+// else is left, otherwise the selection decides.
+pub fn select(selection: MoveSelection, moves: &[RootStats]) -> Option<Dir> {
+    if let Some(win) = moves.iter().find(|stats| stats.proven == Some(true)) {
+        return Some(win.dir);
+    }
+    let open: Vec<&RootStats> = moves
+        .iter()
+        .filter(|stats| stats.proven != Some(false))
+        .collect();
+    let candidates = if open.is_empty() {
+        moves.iter().collect()
+    } else {
+        open
+    };
+    candidates
+        .into_iter()
+        .max_by(|a, b| a.score(selection).total_cmp(&b.score(selection)))
+        .map(|stats| stats.dir)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn stats(dir: Dir, sims: i32, wins: f64) -> RootStats {
+        RootStats {
+            dir,
+            sims,
+            wins,
+            proven: None,
+        }
+    }
+
+    fn moves() -> Vec<RootStats> {
+        vec![
+            // Visited most, but not the best win rate.
+            stats((1, 0), 100, 50.0),
+            // The best win rate, from very few visits.
+            stats((0, 1), 4, 3.0),
+            // A little less often and a little better than up.
+            stats((-1, 0), 90, 54.0),
+        ]
+    }
+
+    #[test]
+    fn test_selections() {
+        let moves = moves();
+        assert_eq!(select(MoveSelection::Robust, &moves), Some((1, 0)));
+        assert_eq!(select(MoveSelection::Max, &moves), Some((0, 1)));
+        assert_eq!(select(MoveSelection::Secure, &moves), Some((-1, 0)));
+        assert_eq!(select(MoveSelection::Weighted, &moves), Some((-1, 0)));
+        assert_eq!(select(MoveSelection::Robust, &[]), None);
+    }
+
+    #[test]
+    fn test_proofs_come_first() {
+        let mut moves = moves();
+        moves[0].proven = Some(false);
+        assert_eq!(select(MoveSelection::Robust, &moves), Some((-1, 0)));
+        moves[1].proven = Some(true);
+        assert_eq!(select(MoveSelection::Robust, &moves), Some((0, 1)));
+        // Every move loses, pick among them as usual.
+        for stats in &mut moves {
+            stats.proven = Some(false);
+        }
+        assert_eq!(select(MoveSelection::Robust, &moves), Some((1, 0)));
+    }
+
+    #[test]
+    fn test_merge_adds_up_trees() {
+        let mut proven = stats((0, 1), 2, 0.0);
+        proven.proven = Some(false);
+        let merged = merge(vec![
+            vec![stats((1, 0), 10, 4.0), stats((0, 1), 5, 1.0)],
+            vec![stats((1, 0), 20, 16.0), proven],
+        ]);
+        let mut expected_right = stats((0, 1), 7, 1.0);
+        expected_right.proven = Some(false);
+        assert_eq!(merged, [expected_right, stats((1, 0), 30, 20.0)]);
+        // The win rate of every visit together, not of each tree.
+        assert_eq!(merged[1].win_rate(), 2.0 / 3.0);
+    }
+}
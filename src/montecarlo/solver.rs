@@ -0,0 +1,210 @@
+use std::sync::atomic::Ordering;
+
+use crate::config::TreePolicy;
+
+use super::{
+    node_state::{NodeId, NodeState},
+    tree::Tree,
+};
+
+// MCTS-Solver. Finished games are proven wins and losses, and proofs move
+// up the tree once a node's children settle it: in the sequential tree the
+// snake to move wins if one of its moves wins, anything else needs all the
+// children to agree. A snake is dead at a node if it dies after every move.
+// Moves left out of the tree, like the ones `feeding_moves` drops, may
+// still turn a game around, so children only agree on a proof once they
+// cover every valid move, or every joint move at a decoupled node.
+impl Tree {
+    pub(super) fn prove(&self, nodes: &[NodeState], id: NodeId) {
+        let node = &nodes[id];
+        if node.children.is_empty() {
+            return;
+        }
+        let children = || node.children.iter().map(|child| &nodes[*child]);
+        let winners: Vec<usize> = children()
+            .map(|child| child.winner.load(Ordering::Relaxed))
+            .collect();
+        if self.tree_policy == TreePolicy::Sequential {
+            let mover = self.snake_tracker.snake_map[&node.current_snake];
+            if winners.contains(&mover) {
+                node.winner.store(mover, Ordering::Relaxed);
+                return;
+            }
+        }
+        if !self.covers_every_move(node) {
+            return;
+        }
+
+        let dead = children()
+            .map(|child| child.dead.load(Ordering::Relaxed))
+            .fold(u64::MAX, |dead, child| dead & child);
+        node.dead.fetch_or(dead, Ordering::Relaxed);
+        if winners.iter().all(|winner| *winner == winners[0])
+            && winners[0] != NodeState::NO_WINNER
+        {
+            node.winner.store(winners[0], Ordering::Relaxed);
+        }
+    }
+
+    fn covers_every_move(&self, node: &NodeState) -> bool {
+        let board = &node.board_state;
+        let valid = |snake_id: &str| board.get_valid_moves(snake_id).len();
+        match self.tree_policy {
+            TreePolicy::Sequential => {
+                node.children.len() == valid(&node.current_snake)
+            }
+            TreePolicy::Decoupled => {
+                node.joint_moves.len()
+                    == board
+                        .snakes
+                        .iter()
+                        .map(|snake| valid(&snake.id))
+                        .product::<usize>()
+            }
+        }
+    }
+
+    // Whether the search can stop, we are proven to win. A proven loss
+    // keeps searching, some losing moves still last longer or place better.
+    pub(super) fn solved(&self) -> bool {
+        self.nodes.read().unwrap()[Tree::ROOT].proof(0) == Some(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::{
+        config::{MonteCarloConfig, TreePolicy},
+        models::GameState,
+        test_utils::scenarios::{get_scenario, place, GET_THE_FOOD},
+    };
+
+    // The other snake is stuck in a corner with its own body all around.
+    fn trapped_other() -> GameState {
+        let mut game_state = get_scenario(GET_THE_FOOD);
+        place(
+            &mut game_state.board,
+            1,
+            &[(0, 0), (1, 0), (1, 1), (0, 1), (0, 2)],
+        );
+        game_state
+    }
+
+    fn config() -> MonteCarloConfig {
+        MonteCarloConfig {
+            max_duration: 10_000,
+            ..MonteCarloConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_stops_at_a_proven_win() {
+        let game_state = trapped_other();
+        let mut tree = Tree::new(config(), game_state.board, game_state.you);
+        let start = Instant::now();
+        let dir = tree.get_best_move();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let root_scores = tree.get_root_scores();
+        let proven = root_scores.iter().find(|stats| stats.dir == dir);
+        assert_eq!(proven.unwrap().proven, Some(true));
+        let nodes = tree.nodes.get_mut().unwrap();
+        assert_eq!(nodes[Tree::ROOT].proof(0), Some(true));
+        assert_eq!(nodes[Tree::ROOT].proof(1), Some(false));
+    }
+
+    #[test]
+    fn test_searches_on_after_a_proven_loss() {
+        let mut game_state = trapped_other();
+        // Swap the snakes, now we are the one stuck.
+        game_state.board.snakes.swap(0, 1);
+        game_state.you = game_state.board.snakes[0].clone();
+        let config = MonteCarloConfig {
+            max_duration: 200,
+            ..MonteCarloConfig::default()
+        };
+        let mut tree = Tree::new(config, game_state.board, game_state.you);
+        let start = Instant::now();
+        tree.get_best_move();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(tree
+            .get_root_scores()
+            .iter()
+            .all(|stats| stats.proven == Some(false)));
+        let nodes = tree.nodes.get_mut().unwrap();
+        assert_eq!(nodes[Tree::ROOT].proof(0), Some(false));
+        assert_eq!(nodes[Tree::ROOT].proof(1), Some(true));
+    }
+
+    #[test]
+    fn test_decoupled_trees_stop_at_a_proven_win() {
+        let game_state = trapped_other();
+        let config = MonteCarloConfig {
+            tree_policy: TreePolicy::Decoupled,
+            ..config()
+        };
+        let mut tree = Tree::new(config, game_state.board, game_state.you);
+        let start = Instant::now();
+        tree.get_best_move();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let nodes = tree.nodes.get_mut().unwrap();
+        assert_eq!(nodes[Tree::ROOT].proof(0), Some(true));
+    }
+
+    #[test]
+    fn test_moves_left_out_prove_nothing() {
+        // Our proof at the root if we lost after every move in the tree.
+        let lost_after_every_child = |game_state: GameState| {
+            let tree = Tree::new(config(), game_state.board, game_state.you);
+            tree.expand(Tree::ROOT);
+            let nodes = tree.nodes.read().unwrap();
+            for child in &nodes[Tree::ROOT].children {
+                nodes[*child].dead.fetch_or(1, Ordering::Relaxed);
+                nodes[*child].winner.store(1, Ordering::Relaxed);
+            }
+            tree.prove(&nodes, Tree::ROOT);
+            (nodes[Tree::ROOT].children.len(), nodes[Tree::ROOT].proof(0))
+        };
+        let game_state = get_scenario(GET_THE_FOOD);
+        assert_eq!(lost_after_every_child(game_state), (3, Some(false)));
+
+        // Left is a move too long to get to the food at (10, 4), so it is
+        // left out of the tree. It might still save us.
+        let mut game_state = get_scenario(GET_THE_FOOD);
+        game_state.board.food.retain(|food| food.x == 10);
+        game_state.you.health = 4;
+        assert_eq!(lost_after_every_child(game_state), (2, None));
+    }
+
+    #[test]
+    fn test_never_selects_proven_losses() {
+        let mut game_state = get_scenario(GET_THE_FOOD);
+        // Left goes into the corner, where we die next turn.
+        place(
+            &mut game_state.board,
+            0,
+            &[(1, 0), (1, 1), (0, 1), (0, 2), (0, 3)],
+        );
+        game_state.you = game_state.board.snakes[0].clone();
+        let mut tree = Tree::new(
+            MonteCarloConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        assert_ne!(tree.get_best_move(), (0, -1));
+
+        let nodes = tree.nodes.get_mut().unwrap();
+        let left = *nodes[Tree::ROOT]
+            .children
+            .iter()
+            .find(|child| nodes[**child].taken_dir == (0, -1))
+            .unwrap();
+        assert_eq!(nodes[left].proof(0), Some(false));
+        let sims = nodes[left].sims();
+        tree.get_best_move();
+        assert_eq!(tree.nodes.get_mut().unwrap()[left].sims(), sims);
+    }
+}
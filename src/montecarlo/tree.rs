@@ -1,27 +1,61 @@
 use rand::seq::SliceRandom;
+use serde::Serialize;
 use std::{
-    collections::HashMap,
-    rc::Rc,
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
     time::{Duration, Instant},
 };
 
 use crate::{
-    config::MonteCarloConfig,
+    config::{MonteCarloConfig, MoveSelection, TreePolicy},
+    evaluation::{self, Evaluator},
     models::{Battlesnake, Board},
+    pathfinding::feeding_moves,
     utils::{self},
 };
 
-use super::node_state::NodeState;
+use super::{
+    node_state::{NodeId, NodeState},
+    rave::Rave,
+    reward::placement_rewards,
+    rollout::{self, RolloutPolicy},
+    selection::{self, RootStats},
+};
 
 pub type Dir = (i32, i32);
 
+// Nodes live in an arena and refer to each other by index, which lets the
+// tree be carried over to the next turn. Threads searching it together
+// only take the write lock to add children, statistics are atomic.
+#[derive(Serialize)]
 pub struct Tree {
-    root: NodeState,
+    // The root comes first.
+    pub(super) nodes: RwLock<Vec<NodeState>>,
+    #[serde(skip)]
     max_duration: u64,
+    #[serde(skip)]
+    threads: usize,
+    #[serde(skip)]
+    pub(super) tree_policy: TreePolicy,
+    #[serde(skip)]
+    pub(super) snake_tracker: SnakeTracker,
+    #[serde(skip)]
+    evaluator: Arc<dyn Evaluator>,
+    #[serde(skip)]
+    rollout: Arc<dyn RolloutPolicy>,
+    #[serde(skip)]
+    rollout_depth: usize,
+    #[serde(skip)]
+    pub(super) rave: Rave,
+    #[serde(skip)]
+    move_selection: MoveSelection,
 }
 
 pub struct SnakeTracker {
-    snake_map: HashMap<String, usize>,
+    pub(super) snake_map: HashMap<String, usize>,
     snake_vec: Vec<String>,
 }
 
@@ -54,6 +88,11 @@ impl SnakeTracker {
 }
 
 impl Tree {
+    pub(super) const ROOT: NodeId = 0;
+    // Visits a playout adds to the nodes it goes through before its
+    // result is known, steering other threads to different lines.
+    pub(super) const VIRTUAL_LOSS: i32 = 1;
+
     pub fn new(
         config: MonteCarloConfig,
         mut starting_board: Board,
@@ -61,42 +100,304 @@ impl Tree {
     ) -> Self {
         let starting_snake_id = starting_snake.id.clone();
         utils::fix_snake_order(&mut starting_board, starting_snake);
-        let snake_tracker = Rc::from(SnakeTracker::new(&starting_board));
-        return Self {
+        let snake_tracker = SnakeTracker::new(&starting_board);
+        let rave = Rave::new(&starting_board, config.rave_equivalence);
+        let root = Tree::root_node(
+            config.tree_policy,
+            &snake_tracker,
+            starting_board,
+            starting_snake_id,
+        );
+        Self {
+            nodes: RwLock::new(vec![root]),
             max_duration: config.max_duration,
-            root: NodeState::new(
-                starting_board,
-                starting_snake_id.clone(),
-                snake_tracker,
-            ),
+            threads: config.threads.max(1),
+            tree_policy: config.tree_policy,
+            snake_tracker,
+            evaluator: evaluation::build(config.evaluator),
+            rollout: rollout::build(config.rollout, config.rollout_epsilon),
+            rollout_depth: config.rollout_depth,
+            rave,
+            move_selection: config.move_selection,
+        }
+    }
+
+    fn root_node(
+        tree_policy: TreePolicy,
+        snake_tracker: &SnakeTracker,
+        board: Board,
+        current_snake: String,
+    ) -> NodeState {
+        if tree_policy == TreePolicy::Decoupled {
+            return NodeState::joint(board, vec![], None);
+        }
+        let snake_who_moved = snake_tracker.get_prev_snake(&current_snake);
+        NodeState::new(
+            board,
+            current_snake.clone(),
+            snake_who_moved.to_owned(),
+            (1, 0),
+            None,
+        )
+    }
+
+    // Moves the root to the position the game reached since the last
+    // search, keeping what was learned below it. Starts over if the tree
+    // never got there, returns whether anything was kept.
+    pub fn advance(&mut self, mut board: Board, you: Battlesnake) -> bool {
+        let nodes = self.nodes.get_mut().unwrap();
+        // Decoupled nodes hold a whole turn each.
+        let depth = match self.tree_policy {
+            TreePolicy::Sequential => self.snake_tracker.snake_vec.len(),
+            TreePolicy::Decoupled => 1,
         };
+        let mut layer = vec![Tree::ROOT];
+        for _ in 0..depth {
+            layer = layer
+                .iter()
+                .flat_map(|id| nodes[*id].children.clone())
+                .collect();
+        }
+        let reached = layer
+            .into_iter()
+            .find(|id| same_position(&nodes[*id].board_state, &board));
+        if let Some(id) = reached {
+            Tree::reroot(nodes, id);
+            return true;
+        }
+        let you_id = you.id.clone();
+        utils::fix_snake_order(&mut board, you);
+        self.snake_tracker = SnakeTracker::new(&board);
+        self.rave = Rave::new(&board, self.rave.equivalence);
+        *nodes = vec![Tree::root_node(
+            self.tree_policy,
+            &self.snake_tracker,
+            board,
+            you_id,
+        )];
+        false
     }
 
-    fn expand_tree(&mut self) {
-        let promising_node = self.root.select_node();
-        promising_node.expand();
-        if promising_node.children.len() > 0 {
-            promising_node
+    // Keeps only the subtree below `id`, which becomes the root.
+    fn reroot(nodes: &mut Vec<NodeState>, id: NodeId) {
+        let mut old: Vec<Option<NodeState>> =
+            std::mem::take(nodes).into_iter().map(Some).collect();
+        let mut pending = VecDeque::from([(id, None)]);
+        while let Some((old_id, parent)) = pending.pop_front() {
+            let mut node = old[old_id].take().unwrap();
+            let new_id = nodes.len();
+            for child in node.children.drain(..) {
+                pending.push_back((child, Some(new_id)));
+            }
+            node.parent = parent;
+            if let Some(parent) = parent {
+                nodes[parent].children.push(new_id);
+            }
+            nodes.push(node);
+        }
+    }
+
+    // Walks down to a leaf, adding a virtual loss to every node on the way.
+    fn select_node(&self) -> NodeId {
+        let nodes = self.nodes.read().unwrap();
+        let evaluator = self.evaluator.as_ref();
+        let mut id = Tree::ROOT;
+        loop {
+            let node = &nodes[id];
+            node.sims.fetch_add(Tree::VIRTUAL_LOSS, Ordering::Relaxed);
+            if node.children.is_empty() {
+                return id;
+            }
+            let parent_sims = node.sims();
+            let mover = self.snake_tracker.snake_map[&node.current_snake];
+            let head = &node.board_state.get_snake(&node.current_snake).head;
+            let value = |child: NodeId| {
+                let child = &nodes[child];
+                let key = self.rave.key(mover, head, child.taken_dir);
+                let rave = self.rave.blend(key, child.sims());
+                child.utc_val(parent_sims, evaluator, rave)
+            };
+            // A proven win is taken right away, proven losses only when
+            // there is nothing else, and then the best of them.
+            let proof = |child: &&NodeId| nodes[**child].proof(mover);
+            if let Some(win) =
+                node.children.iter().find(|c| proof(c) == Some(true))
+            {
+                id = *win;
+                continue;
+            }
+            let all_lost =
+                node.children.iter().all(|c| proof(&c) == Some(false));
+            id = *node
                 .children
-                .choose_mut(&mut rand::thread_rng())
-                .unwrap()
-                .play_out();
+                .iter()
+                .filter(|child| all_lost || proof(child) != Some(false))
+                .max_by(|x, y| value(**x).total_cmp(&value(**y)))
+                .unwrap();
+        }
+    }
+
+    // Adds the children of a leaf, unless another thread got there first.
+    pub(super) fn expand(&self, id: NodeId) {
+        let mut children = vec![];
+        {
+            let nodes = self.nodes.read().unwrap();
+            let node = &nodes[id];
+            if node.board_state.is_terminal() || !node.children.is_empty() {
+                return;
+            }
+            let current_snake = &node.current_snake;
+            let next_snake = self.snake_tracker.get_next_snake(current_snake);
+            for dir in feeding_moves(&node.board_state, current_snake) {
+                let mut new_board = node.board_state.clone();
+                new_board.execute_dir(current_snake, dir);
+                children.push(NodeState::new(
+                    new_board,
+                    next_snake.to_string(),
+                    current_snake.clone(),
+                    dir,
+                    Some(id),
+                ));
+            }
+        }
+        let mut nodes = self.nodes.write().unwrap();
+        if !nodes[id].children.is_empty() {
             return;
         }
-        promising_node.play_out();
+        for child in children {
+            let child_id = nodes.len();
+            nodes.push(child);
+            nodes[id].children.push(child_id);
+        }
     }
 
-    pub fn get_root_scores(&self) -> Vec<(Dir, i32)> {
-        let mut dirs = vec![];
-        for child in &self.root.children {
-            dirs.push((child.taken_dir, child.sims))
+    // Plays the game on until it is over, or for `rollout_depth` turns if
+    // set. Returns every snake's reward for its placement in board order,
+    // and the RAVE keys of the moves played. Snakes that were dead before
+    // the playout share the last places.
+    pub(super) fn play_out(&self, id: NodeId) -> (Vec<f32>, Vec<usize>) {
+        let (mut board_copy, mut current_snake) = {
+            let nodes = self.nodes.read().unwrap();
+            (
+                nodes[id].board_state.clone(),
+                nodes[id].current_snake.clone(),
+            )
+        };
+        let mut died_at: Vec<Option<usize>> = board_copy
+            .snakes
+            .iter()
+            .map(|snake| snake.eliminated_cause.as_ref().map(|_| 0))
+            .collect();
+        // Only whole turns are scored, a turn ends with the last snake. A
+        // turn already under way counts as the first.
+        let first_snake = &self.snake_tracker.snake_vec[0];
+        let mut turns = usize::from(current_snake != *first_snake);
+        let mut keys = vec![];
+        while !board_copy.get_endstate().is_terminal() {
+            if current_snake == *first_snake {
+                if self.rollout_depth > 0 && turns >= self.rollout_depth {
+                    break;
+                }
+                turns += 1;
+            }
+            let head = board_copy.get_snake(&current_snake).head.clone();
+            let dir = self.rollout.play(&mut board_copy, &current_snake);
+            let mover = self.snake_tracker.snake_map[&current_snake];
+            keys.extend(self.rave.key(mover, &head, dir));
+            for (died, snake) in died_at.iter_mut().zip(&board_copy.snakes) {
+                if died.is_none() && snake.eliminated_cause.is_some() {
+                    *died = Some(turns);
+                }
+            }
+            current_snake = self
+                .snake_tracker
+                .get_next_snake(&current_snake)
+                .to_string();
+        }
+        let shares = self.evaluator.evaluate(&board_copy);
+        (placement_rewards(&died_at, &shares), keys)
+    }
+
+    // Replaces the virtual losses on the way down with the real result.
+    fn back_prop(&self, id: NodeId, rewards: &[f32], mut keys: Vec<usize>) {
+        let nodes = self.nodes.read().unwrap();
+        let mut next = Some(id);
+        while let Some(id) = next {
+            let node = &nodes[id];
+            let mover = self.snake_tracker.snake_map[&node.snake_who_moved];
+            node.wins.fetch_add(rewards[mover] as f64);
+            node.sims
+                .fetch_add(1 - Tree::VIRTUAL_LOSS, Ordering::Relaxed);
+            self.prove(&nodes, id);
+            next = node.parent;
+            if let (Some(parent), true) = (next, self.rave.enabled()) {
+                let from = &nodes[parent].board_state;
+                let head = &from.get_snake(&node.snake_who_moved).head;
+                keys.extend(self.rave.key(mover, head, node.taken_dir));
+            }
+        }
+        if self.rave.enabled() {
+            self.rave.update(keys, rewards);
+        }
+    }
+
+    fn expand_tree(&self) {
+        if self.tree_policy == TreePolicy::Decoupled {
+            return self.decoupled_iteration();
+        }
+        let promising_node = self.select_node();
+        self.expand(promising_node);
+        let child = self.nodes.read().unwrap()[promising_node]
+            .children
+            .choose(&mut rand::thread_rng())
+            .copied();
+        let leaf = match child {
+            // The chosen child is visited too.
+            Some(child) => {
+                self.nodes.read().unwrap()[child]
+                    .sims
+                    .fetch_add(Tree::VIRTUAL_LOSS, Ordering::Relaxed);
+                child
+            }
+            None => promising_node,
+        };
+        let (rewards, keys) = self.play_out(leaf);
+        self.back_prop(leaf, &rewards, keys);
+    }
+
+    pub fn get_root_scores(&self) -> Vec<RootStats> {
+        let nodes = self.nodes.read().unwrap();
+        if self.tree_policy == TreePolicy::Decoupled {
+            // We come first on the board.
+            return nodes[Tree::ROOT]
+                .snake_moves
+                .first()
+                .into_iter()
+                .flatten()
+                .map(|stats| RootStats {
+                    dir: stats.dir,
+                    sims: stats.sims.load(Ordering::Relaxed),
+                    wins: stats.wins.load(),
+                    proven: None,
+                })
+                .collect();
         }
-        return dirs;
+        nodes[Tree::ROOT]
+            .children
+            .iter()
+            .map(|child| &nodes[*child])
+            .map(|child| RootStats {
+                dir: child.taken_dir,
+                sims: child.sims.load(Ordering::Relaxed),
+                wins: child.wins(),
+                proven: child.proof(0),
+            })
+            .collect()
     }
 
     #[cfg(test)]
     pub fn get_best_move(&mut self) -> (i32, i32) {
-        return self.get_best_move_with_start_time(Instant::now());
+        self.get_best_move_with_start_time(Instant::now())
     }
 
     pub fn get_best_move_with_start_time(
@@ -104,36 +405,67 @@ impl Tree {
         start: Instant,
     ) -> (i32, i32) {
         let max_duration = Duration::from_millis(self.max_duration);
-        self.root.expand();
-        let mut i = 0;
-        loop {
+        if self.tree_policy == TreePolicy::Sequential {
+            self.expand(Tree::ROOT);
+        }
+        let iterations = AtomicUsize::new(0);
+        let search = || loop {
             self.expand_tree();
-            let elasped_time = start.elapsed();
-            if elasped_time >= max_duration {
+            if start.elapsed() >= max_duration || self.solved() {
                 break;
             }
-            i += 1
-        }
-        info!("Ran for {} iterations", i);
-        let best_child =
-            self.root.children.iter().max_by(|x, y| x.sims.cmp(&y.sims));
-        if best_child.is_none() {
-            return (1, 0);
+            iterations.fetch_add(1, Ordering::Relaxed);
+        };
+        if self.threads > 1 {
+            rayon::scope(|scope| {
+                for _ in 0..self.threads {
+                    scope.spawn(|_| search());
+                }
+            });
+        } else {
+            search();
         }
-        let best_child = best_child.unwrap();
-        return best_child.taken_dir;
+        info!(
+            "Ran for {} iterations on {} threads",
+            iterations.into_inner(),
+            self.threads
+        );
+        selection::select(self.move_selection, &self.get_root_scores())
+            .unwrap_or((1, 0))
     }
 }
 
+// A board the game server sent, which leaves out dead snakes, shows the
+// same position as a board in the tree.
+fn same_position(node_board: &Board, board: &Board) -> bool {
+    let alive: Vec<&Battlesnake> = node_board
+        .snakes
+        .iter()
+        .filter(|snake| snake.eliminated_cause.is_none())
+        .collect();
+    alive.len() == board.snakes.len()
+        && board.snakes.iter().all(|snake| {
+            alive.iter().any(|other| {
+                other.id == snake.id
+                    && other.health == snake.health
+                    && other.body == snake.body
+            })
+        })
+        && node_board.food.len() == board.food.len()
+        && board.food.iter().all(|food| node_board.food.contains(food))
+}
+
 #[cfg(test)]
 mod test {
 
     use super::*;
     use crate::{
+        config::EvaluatorKind,
+        models::Coord,
         test_utils::scenarios::{
-            get_board, get_scenario, AVOID_DEATH_ADVANCED,
+            get_board, get_scenario, made_up_board, AVOID_DEATH_ADVANCED,
             AVOID_DEATH_GET_FOOD, AVOID_HEAD_TO_HEAD_DEATH, AVOID_SELF_TRAP,
-            DO_NOT_CIRCLE_FOOD, GET_THE_FOOD, MULTI_SNAKE,
+            DO_NOT_CIRCLE_FOOD, FOUR_SNAKES, GET_THE_FOOD, MULTI_SNAKE,
         },
         utils::dir_to_string,
     };
@@ -231,4 +563,187 @@ mod test {
         );
         assert_eq!(dir_to_string(tree.get_best_move()).is_empty(), false);
     }
+
+    #[test]
+    fn test_reuses_the_tree_across_turns() {
+        let game_state = get_scenario(GET_THE_FOOD);
+        let you = game_state.you.clone();
+        let mut tree = Tree::new(
+            MonteCarloConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        tree.get_best_move();
+
+        // Follow the most visited moves through a whole turn.
+        let turn = tree.snake_tracker.snake_vec.len();
+        let nodes = tree.nodes.get_mut().unwrap();
+        let mut id = Tree::ROOT;
+        for _ in 0..turn {
+            id = *nodes[id]
+                .children
+                .iter()
+                .max_by_key(|child| nodes[**child].sims.load(Ordering::Relaxed))
+                .unwrap();
+        }
+        let sims = nodes[id].sims();
+        let mut board = nodes[id].board_state.clone();
+        board
+            .snakes
+            .retain(|snake| snake.eliminated_cause.is_none());
+        board.food.reverse();
+
+        assert!(tree.advance(board.clone(), you.clone()));
+        let nodes = tree.nodes.get_mut().unwrap();
+        assert_eq!(nodes[Tree::ROOT].sims(), sims);
+        assert_eq!(nodes[Tree::ROOT].parent, None);
+        for (id, node) in nodes.iter().enumerate() {
+            for child in &node.children {
+                assert_eq!(nodes[*child].parent, Some(id));
+            }
+        }
+
+        // Food that appeared in the meantime is a position never searched.
+        board.food.push(Coord { x: 0, y: 0 });
+        assert!(!tree.advance(board, you));
+        assert_eq!(tree.nodes.get_mut().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_threads_share_one_tree() {
+        let game_state = get_scenario(GET_THE_FOOD);
+        let config = MonteCarloConfig {
+            threads: 2,
+            ..MonteCarloConfig::default()
+        };
+        let mut tree = Tree::new(config, game_state.board, game_state.you);
+        tree.get_best_move();
+
+        // Every virtual loss was replaced by a real visit. Visits go through
+        // children, apart from a playout a node may get before expanding.
+        let nodes = tree.nodes.get_mut().unwrap();
+        assert!(nodes[Tree::ROOT].sims() > 0.0);
+        for (id, node) in nodes.iter().enumerate() {
+            assert!(node.wins() <= node.sims());
+            if node.children.is_empty() {
+                continue;
+            }
+            let below: f64 =
+                node.children.iter().map(|child| nodes[*child].sims()).sum();
+            let own = if id == Tree::ROOT {
+                0.0..=0.0
+            } else {
+                0.0..=1.0
+            };
+            assert!(own.contains(&(node.sims() - below)));
+        }
+    }
+
+    #[test]
+    fn test_cut_off_playouts_share_the_reward() {
+        let game_state = get_scenario(GET_THE_FOOD);
+        let config = MonteCarloConfig {
+            evaluator: EvaluatorKind::Fill,
+            rollout_depth: 1,
+            ..MonteCarloConfig::default()
+        };
+        let tree = Tree::new(config, game_state.board, game_state.you);
+        // Nobody dies in one turn, the evaluator splits the board.
+        let (rewards, _) = tree.play_out(Tree::ROOT);
+        assert_eq!(rewards.len(), 2);
+        assert!(rewards.iter().all(|reward| *reward > 0.0 && *reward < 1.0));
+        assert!((rewards.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_playouts_reward_every_place() {
+        let game_state = get_scenario(FOUR_SNAKES);
+        let tree = Tree::new(
+            MonteCarloConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        for _ in 0..20 {
+            let (rewards, _) = tree.play_out(Tree::ROOT);
+            assert!(rewards.iter().all(|reward| (0.0..=1.0).contains(reward)));
+            // Places 1 to 4 are worth 1, 2/3, 1/3 and nothing.
+            assert!((rewards.iter().sum::<f32>() - 2.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_snakes_dying_in_the_same_turn_share_their_place() {
+        let mut board = made_up_board(
+            11,
+            11,
+            &[
+                ("you", &[(5, 5), (5, 4), (5, 3)]),
+                // Walled in by the starving snake, dies on its own move.
+                ("trapped", &[(0, 0), (0, 1), (0, 2)]),
+                // Runs out of health when the turn ends, two moves later.
+                ("starving", &[(3, 0), (2, 0), (1, 0), (1, 1), (1, 2)]),
+            ],
+        );
+        board.snakes[2].health = 1;
+        let you = board.snakes[0].clone();
+        let tree = Tree::new(MonteCarloConfig::default(), board, you);
+        let (rewards, _) = tree.play_out(Tree::ROOT);
+        assert_eq!(rewards, [1.0, 0.25, 0.25]);
+    }
+
+    #[test]
+    fn test_depth_limited_search_backs_up_shares() {
+        let game_state = get_scenario(GET_THE_FOOD);
+        let config = MonteCarloConfig {
+            evaluator: EvaluatorKind::Fill,
+            rollout_depth: 10,
+            ..MonteCarloConfig::default()
+        };
+        let mut tree = Tree::new(config, game_state.board, game_state.you);
+        tree.get_best_move();
+        let nodes = tree.nodes.get_mut().unwrap();
+        assert!(nodes.iter().any(|node| node.wins().fract() != 0.0));
+        for node in nodes.iter() {
+            assert!(node.wins() <= node.sims());
+        }
+    }
+
+    #[test]
+    fn test_starving_moves_are_not_expanded() {
+        let mut game_state = get_scenario(GET_THE_FOOD);
+        // Left is a move too long to get to the food at (10, 4).
+        game_state.board.food.retain(|food| food.x == 10);
+        game_state.you.health = 4;
+        let tree = Tree::new(
+            MonteCarloConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        tree.expand(Tree::ROOT);
+        let nodes = tree.nodes.read().unwrap();
+        let dirs: Vec<Dir> = nodes[Tree::ROOT]
+            .children
+            .iter()
+            .map(|child| nodes[*child].taken_dir)
+            .collect();
+        assert_eq!(dirs, [(0, 1), (-1, 0)]);
+    }
+
+    #[test]
+    fn test_tree_can_be_sent_and_dumped() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Tree>();
+
+        let game_state = get_scenario(GET_THE_FOOD);
+        let mut tree = Tree::new(
+            MonteCarloConfig::default(),
+            game_state.board,
+            game_state.you,
+        );
+        tree.get_best_move();
+        let dump = serde_json::to_value(&tree).unwrap();
+        assert!(dump["nodes"][0]["sims"].as_i64().unwrap() > 0);
+        assert_eq!(dump["nodes"][1]["parent"], 0);
+        assert!(dump["nodes"][1]["taken_dir"].is_string());
+    }
 }
@@ -0,0 +1,316 @@
+use serde::Serialize;
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::{
+    floodfill::moves_until_free,
+    models::{Board, Coord},
+    utils::{self, serialize_dir},
+};
+
+// Health lost per turn spent in a hazard, on top of the usual one.
+pub const HAZARD_DAMAGE: u32 = 14;
+// Extra cost of a step next to the head of a snake at least as long as us.
+const DANGER_COST: u32 = 10;
+// At or below this health a snake may starve before it gets to food, so
+// it heads for food and the way there counts.
+pub const HUNGRY: u32 = 40;
+
+// The cheapest way to food found by `nearest_food`.
+#[derive(Clone, Debug, Serialize)]
+pub struct FoodPath {
+    pub food: Coord,
+    #[serde(serialize_with = "serialize_dir")]
+    pub first_move: (i32, i32),
+    // Turns until we eat.
+    pub turns: usize,
+    // Turns plus the penalties of hazards and dangerous cells on the way.
+    pub cost: u32,
+}
+
+// Best way found to a cell so far.
+#[derive(Clone, Copy)]
+struct Step {
+    cost: u32,
+    turns: usize,
+    health: u32,
+    first_move: (i32, i32),
+}
+
+// A* from the head to the closest food by cost. Cells still occupied by a
+// body when we would get there are walls, and so are cells where we would
+// run out of health. Other snakes are assumed to stay out of the way, apart
+// from the penalty for passing next to heads that beat ours.
+pub fn nearest_food(board: &Board, snake_id: &str) -> Option<FoodPath> {
+    search(board, snake_id, &board.food, None)
+}
+
+// The valid moves after which the snake still gets to food before it
+// starves. Only hungry snakes lose moves, and when no move gets there the
+// snake is lost anyway so they are all kept.
+pub fn feeding_moves(board: &Board, snake_id: &str) -> Vec<(i32, i32)> {
+    let moves = board.get_valid_moves(snake_id);
+    let snake = board.get_snake(snake_id);
+    if snake.health > HUNGRY || snake.eliminated_cause.is_some() {
+        return moves;
+    }
+    let feeding: Vec<_> = moves
+        .iter()
+        .copied()
+        .filter(|dir| {
+            search(board, snake_id, &board.food, Some(*dir)).is_some()
+        })
+        .collect();
+    if feeding.is_empty() {
+        return moves;
+    }
+    feeding
+}
+
+// Nearest food we get to before starving and before any other snake. A
+// snake whose head is closer, or as close and at least as long, takes it.
+pub fn food_in_time(board: &Board, snake_id: &str) -> Option<FoodPath> {
+    let snake = board.get_snake(snake_id);
+    let mut food = board.food.clone();
+    while let Some(path) = search(board, snake_id, &food, None) {
+        let taken = board.snakes.iter().any(|other| {
+            let distance = (other.head.x - path.food.x).abs()
+                + (other.head.y - path.food.y).abs();
+            other.id != snake.id
+                && other.eliminated_cause.is_none()
+                && (distance < path.turns as i32
+                    || distance == path.turns as i32
+                        && other.body.len() >= snake.body.len())
+        });
+        if !taken {
+            return Some(path);
+        }
+        food.retain(|coord| *coord != path.food);
+    }
+    None
+}
+
+// With `first` set, only paths starting with that move count.
+fn search(
+    board: &Board,
+    snake_id: &str,
+    food: &[Coord],
+    first: Option<(i32, i32)>,
+) -> Option<FoodPath> {
+    let snake = board.get_snake(snake_id);
+    let (width, height) = (board.width(), board.height());
+    if food.is_empty()
+        || snake.eliminated_cause.is_some()
+        || !snake.head.in_bounds(width, height)
+    {
+        return None;
+    }
+    let index = |coord: &Coord| coord.y() * width as usize + coord.x();
+    let cells = (width * height) as usize;
+
+    let free_after = moves_until_free(board);
+    let mut has_food = vec![false; cells];
+    for coord in food {
+        has_food[index(coord)] = true;
+    }
+    let mut hazard = vec![false; cells];
+    for coord in &board.hazards {
+        if coord.in_bounds(width, height) {
+            hazard[index(coord)] = true;
+        }
+    }
+    let mut danger = vec![false; cells];
+    for other in &board.snakes {
+        if other.id == snake.id
+            || other.eliminated_cause.is_some()
+            || other.body.len() < snake.body.len()
+        {
+            continue;
+        }
+        for (y, x) in utils::DIRECTIONS {
+            let next = Coord {
+                x: other.head.x + x,
+                y: other.head.y + y,
+            };
+            if next.in_bounds(width, height) {
+                danger[index(&next)] = true;
+            }
+        }
+    }
+    // Every step costs at least one, so the distance to the closest food
+    // never overestimates.
+    let estimate = |coord: &Coord| {
+        food.iter()
+            .map(|food| (food.x - coord.x).abs() + (food.y - coord.y).abs())
+            .min()
+            .unwrap_or(0) as u32
+    };
+
+    let mut best: Vec<Option<Step>> = vec![None; cells];
+    best[index(&snake.head)] = Some(Step {
+        cost: 0,
+        turns: 0,
+        health: snake.health,
+        first_move: (0, 0),
+    });
+    let mut open = BinaryHeap::new();
+    open.push(Reverse((
+        estimate(&snake.head),
+        0,
+        snake.head.x,
+        snake.head.y,
+    )));
+    while let Some(Reverse((_, cost, x, y))) = open.pop() {
+        let coord = Coord { x, y };
+        let step = best[index(&coord)].unwrap();
+        // Already reached more cheaply.
+        if cost > step.cost {
+            continue;
+        }
+        if step.turns > 0 && has_food[index(&coord)] {
+            return Some(FoodPath {
+                food: coord,
+                first_move: step.first_move,
+                turns: step.turns,
+                cost,
+            });
+        }
+        for dir in utils::DIRECTIONS {
+            if step.turns == 0 && matches!(first, Some(first) if first != dir) {
+                continue;
+            }
+            let next = Coord {
+                x: coord.x + dir.1,
+                y: coord.y + dir.0,
+            };
+            if !next.in_bounds(width, height) {
+                continue;
+            }
+            let cell = index(&next);
+            let turns = step.turns + 1;
+            if free_after[cell] > turns {
+                continue;
+            }
+            let damage = if hazard[cell] { 1 + HAZARD_DAMAGE } else { 1 };
+            let health = step.health.saturating_sub(damage);
+            // Eating comes before starving within a turn.
+            if health == 0 && !has_food[cell] {
+                continue;
+            }
+            let mut next_cost = cost + 1;
+            if hazard[cell] {
+                next_cost += HAZARD_DAMAGE;
+            }
+            if danger[cell] {
+                next_cost += DANGER_COST;
+            }
+            if matches!(best[cell], Some(known) if known.cost <= next_cost) {
+                continue;
+            }
+            best[cell] = Some(Step {
+                cost: next_cost,
+                turns,
+                health,
+                first_move: if step.turns == 0 {
+                    dir
+                } else {
+                    step.first_move
+                },
+            });
+            open.push(Reverse((
+                next_cost + estimate(&next),
+                next_cost,
+                next.x,
+                next.y,
+            )));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::scenarios::{get_scenario, GET_THE_FOOD};
+
+    #[test]
+    fn test_goes_for_the_closest_food() {
+        let game_state = get_scenario(GET_THE_FOOD);
+        let path = nearest_food(&game_state.board, &game_state.you.id).unwrap();
+        assert_eq!(path.food, Coord { x: 9, y: 6 });
+        assert_eq!(path.first_move, (-1, 0));
+        assert_eq!(path.turns, 1);
+        assert_eq!(path.cost, 1);
+    }
+
+    #[test]
+    fn test_walks_around_hazards() {
+        let mut game_state = get_scenario(GET_THE_FOOD);
+        game_state.board.hazards.push(Coord { x: 9, y: 6 });
+        let path = nearest_food(&game_state.board, &game_state.you.id).unwrap();
+        assert_eq!(path.food, Coord { x: 10, y: 4 });
+        assert_eq!(path.first_move, (0, 1));
+        assert_eq!(path.turns, 4);
+
+        // Out of time to go around, eating makes up for the damage.
+        game_state.board.snakes[0].health = 1;
+        let path = nearest_food(&game_state.board, &game_state.you.id).unwrap();
+        assert_eq!(path.food, Coord { x: 9, y: 6 });
+        assert_eq!(path.cost, 1 + HAZARD_DAMAGE);
+    }
+
+    #[test]
+    fn test_keeps_away_from_longer_heads() {
+        let mut game_state = get_scenario(GET_THE_FOOD);
+        let board = &mut game_state.board;
+        // Only the food at (5, 5) is left, right under the longer snake.
+        board.food.retain(|food| *food == Coord { x: 5, y: 5 });
+        let direct = nearest_food(board, &game_state.you.id).unwrap();
+        board.snakes[1].head = Coord { x: 5, y: 6 };
+        let guarded = nearest_food(board, &game_state.you.id).unwrap();
+        assert_eq!(guarded.turns, direct.turns);
+        assert_eq!(guarded.cost, direct.cost + DANGER_COST);
+
+        board.food.clear();
+        assert!(nearest_food(board, &game_state.you.id).is_none());
+    }
+
+    #[test]
+    fn test_food_others_get_first_does_not_count() {
+        let game_state = get_scenario(GET_THE_FOOD);
+        let (board, you) = (&game_state.board, &game_state.you.id);
+        assert_eq!(food_in_time(board, you).unwrap().turns, 1);
+
+        // The other snake is as close to (9, 6) as we are, and closer to
+        // the rest of the food. It only gets the food if it is longer.
+        let mut board = board.clone();
+        board.snakes[1].head = Coord { x: 8, y: 6 };
+        assert!(food_in_time(&board, you).is_none());
+        board.snakes[1].body.truncate(3);
+        let path = food_in_time(&board, you).unwrap();
+        assert_eq!(path.food, Coord { x: 9, y: 6 });
+
+        // Without the health to get to food nobody else wants we starve.
+        board.food.retain(|food| *food == Coord { x: 10, y: 4 });
+        board.snakes[1].head = Coord { x: 2, y: 2 };
+        assert_eq!(food_in_time(&board, you).unwrap().turns, 4);
+        board.snakes[0].health = 3;
+        assert!(food_in_time(&board, you).is_none());
+    }
+
+    #[test]
+    fn test_drops_moves_that_starve() {
+        let mut game_state = get_scenario(GET_THE_FOOD);
+        let (board, you) = (&mut game_state.board, &game_state.you.id);
+        // The food left at (10, 4) is four moves away going down or right,
+        // five going left.
+        board.food.retain(|food| food.x == 10);
+        assert_eq!(feeding_moves(board, you).len(), 3);
+        board.snakes[0].health = 4;
+        assert_eq!(feeding_moves(board, you), [(0, 1), (-1, 0)]);
+
+        // Too late whatever we do, nothing to choose between.
+        board.snakes[0].health = 3;
+        assert_eq!(feeding_moves(board, you), board.get_valid_moves(you));
+    }
+}
@@ -1,5 +1,3 @@
-use rand::seq::SliceRandom;
-
 use crate::{
     models::{Battlesnake, Board, Coord},
     utils,
@@ -34,12 +32,6 @@ impl EndState {
 }
 
 impl Board {
-    pub fn execute_random_move(&mut self, snake_id: &str) {
-        let valid_moves = self.get_valid_moves(snake_id);
-        let dir = valid_moves.choose(&mut rand::thread_rng()).unwrap();
-        self.execute_dir(snake_id, dir.clone());
-    }
-
     pub fn get_valid_moves(&self, snake_id: &str) -> Vec<(i32, i32)> {
         let mut dirs = vec![];
         let snake = self.get_snake(snake_id);
@@ -340,6 +332,15 @@ impl Board {
         }
         panic!("Snake not found")
     }
+
+    // Snakes still on the board, with their index. Dead snakes are removed
+    // from the board, and may have left it.
+    pub fn live_snakes(&self) -> impl Iterator<Item = (usize, &Battlesnake)> {
+        self.snakes
+            .iter()
+            .enumerate()
+            .filter(|(_, snake)| snake.eliminated_cause.is_none())
+    }
 }
 
 impl Battlesnake {
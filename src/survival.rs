@@ -0,0 +1,173 @@
+use crate::{
+    floodfill::moves_until_free,
+    models::{Board, Coord},
+    utils,
+};
+
+// How many turns a snake can keep moving in its region when nobody else
+// gets in the way. This is the longest self-avoiding path of its head, where
+// cells free up as bodies (its own included) move out of the way. The search
+// is exact while it fits the node budget, past it the longest path found so
+// far is a lower bound.
+#[derive(Debug)]
+pub struct Survival {
+    pub turns: usize,
+    pub exact: bool,
+}
+
+// Nodes the path search may visit before settling for an estimate.
+const NODE_BUDGET: usize = 50_000;
+
+pub fn survival(board: &Board, snake_id: &str, horizon: usize) -> Survival {
+    let mut solver = Solver::new(board, snake_id, horizon);
+    let start = board.get_snake(snake_id).head.clone();
+    let turns = solver.longest(&start, 0);
+    Survival {
+        turns,
+        exact: solver.nodes <= NODE_BUDGET,
+    }
+}
+
+// Stall policy for a snake alone in its region: the move that keeps it
+// alive the longest. None if every move is fatal.
+pub fn stall_move(board: &Board, snake_id: &str) -> Option<(i32, i32)> {
+    let snake = board.get_snake(snake_id);
+    let horizon = (board.width * board.height) as usize;
+    let mut best: Option<((i32, i32), usize)> = None;
+    for dir in utils::DIRECTIONS {
+        let next = Coord {
+            x: snake.head.x + dir.1,
+            y: snake.head.y + dir.0,
+        };
+        // Every move gets a budget of its own.
+        let mut solver = Solver::new(board, snake_id, horizon);
+        let Some(cell) = solver.enterable(&next, 1) else {
+            continue;
+        };
+        solver.visited_at[cell] = Some(1);
+        let turns = solver.longest(&next, 1);
+        if !matches!(best, Some((_, most)) if most >= turns) {
+            best = Some((dir, turns));
+        }
+    }
+    best.map(|(dir, _)| dir)
+}
+
+struct Solver {
+    width: usize,
+    height: usize,
+    // Moves until each cell is free of the bodies on the board.
+    free_after: Vec<usize>,
+    // Turn our head entered a cell on the current path.
+    visited_at: Vec<Option<usize>>,
+    length: usize,
+    horizon: usize,
+    nodes: usize,
+}
+
+impl Solver {
+    fn new(board: &Board, snake_id: &str, horizon: usize) -> Self {
+        let width = board.width as usize;
+        let height = board.height as usize;
+        Self {
+            width,
+            height,
+            free_after: moves_until_free(board),
+            visited_at: vec![None; width * height],
+            length: board.get_snake(snake_id).body.len(),
+            horizon,
+            nodes: 0,
+        }
+    }
+
+    // Cell index of `coord` if the head can be there after `turn` moves.
+    fn enterable(&self, coord: &Coord, turn: usize) -> Option<usize> {
+        if !coord.in_bounds(self.width as i32, self.height as i32) {
+            return None;
+        }
+        let cell = coord.y() * self.width + coord.x();
+        let left_behind = match self.visited_at[cell] {
+            Some(visited) => turn >= visited + self.length,
+            None => true,
+        };
+        (self.free_after[cell] <= turn && left_behind).then_some(cell)
+    }
+
+    fn successors(&self, from: &Coord, turn: usize) -> Vec<(Coord, usize)> {
+        utils::DIRECTIONS
+            .iter()
+            .filter_map(|(y, x)| {
+                let next = Coord {
+                    x: from.x + x,
+                    y: from.y + y,
+                };
+                self.enterable(&next, turn).map(|cell| (next, cell))
+            })
+            .collect()
+    }
+
+    // Longest path from `from`, reached after `turn` moves. Cells with the
+    // fewest ways out go first, which hugs walls and finds long paths early
+    // when the budget runs out.
+    fn longest(&mut self, from: &Coord, turn: usize) -> usize {
+        self.nodes += 1;
+        if turn >= self.horizon || self.nodes > NODE_BUDGET {
+            return turn;
+        }
+        let mut moves = self.successors(from, turn + 1);
+        moves.sort_by_cached_key(|(next, _)| {
+            self.successors(next, turn + 2).len()
+        });
+        let mut best = turn;
+        for (next, cell) in moves {
+            let previous = self.visited_at[cell].replace(turn + 1);
+            best = best.max(self.longest(&next, turn + 1));
+            self.visited_at[cell] = previous;
+            if best >= self.horizon {
+                break;
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::scenarios::made_up_board;
+
+    // Just us on a small board.
+    fn alone(width: u32, height: u32, body: &[(i32, i32)]) -> Board {
+        made_up_board(width, height, &[("you", body)])
+    }
+
+    #[test]
+    fn test_corridor_has_one_way_out() {
+        // A 6x1 corridor at the start of a game, there is no turning around.
+        let board = alone(6, 1, &[(2, 0), (2, 0), (2, 0)]);
+        let snake_id = board.snakes[0].id.clone();
+        let result = survival(&board, &snake_id, 25);
+        // Five free cells, but only the three to the right are one path.
+        assert_eq!(result.turns, 3);
+        assert!(result.exact);
+        assert_eq!(stall_move(&board, &snake_id), Some((0, 1)));
+    }
+
+    #[test]
+    fn test_chasing_the_tail_survives() {
+        // Going round a 2x2 square never ends.
+        let board = alone(2, 2, &[(0, 0), (0, 1), (1, 1)]);
+        let snake_id = board.snakes[0].id.clone();
+        assert_eq!(survival(&board, &snake_id, 20).turns, 20);
+        assert_eq!(stall_move(&board, &snake_id), Some((0, 1)));
+    }
+
+    #[test]
+    fn test_no_way_out() {
+        let board = alone(2, 1, &[(0, 0), (1, 0), (1, 0)]);
+        let snake_id = board.snakes[0].id.clone();
+        assert_eq!(survival(&board, &snake_id, 20).turns, 0);
+        assert_eq!(stall_move(&board, &snake_id), None);
+    }
+}
@@ -1,7 +1,7 @@
 #[cfg(test)]
 pub mod scenarios {
 
-    use crate::models::GameState;
+    use crate::models::{Battlesnake, Board, Coord, GameState};
     use std::{fs, path::PathBuf};
 
     const TEST_REQUEST: &str = "./scenarios/test_request.json";
@@ -16,6 +16,7 @@ pub mod scenarios {
     pub const AVOID_HEAD_TO_HEAD_DEATH: &str =
         "./scenarios/avoid_head_to_head_death.json";
     pub const MULTI_SNAKE: &str = "./scenarios/multi_snake.json";
+    pub const FOUR_SNAKES: &str = "./scenarios/four_snakes.json";
 
     pub fn load_game_state(path: &str) -> GameState {
         let full_path = PathBuf::from(path);
@@ -37,4 +38,38 @@ pub mod scenarios {
     pub fn get_scenario(path: &str) -> GameState {
         load_game_state(path)
     }
+
+    // Moves a snake to a made up position, its body given head first as
+    // (x, y) pairs.
+    pub fn place(board: &mut Board, snake: usize, body: &[(i32, i32)]) {
+        let snake = &mut board.snakes[snake];
+        snake.body = body.iter().map(|&(x, y)| Coord { x, y }).collect();
+        snake.head = snake.body[0].clone();
+        snake.length = body.len() as u32;
+    }
+
+    // An empty board with the given snakes, each a copy of the first snake
+    // of the test request with its own id and body.
+    pub fn made_up_board(
+        width: u32,
+        height: u32,
+        snakes: &[(&str, &[(i32, i32)])],
+    ) -> Board {
+        let mut board = get_board().board;
+        board.width = width;
+        board.height = height;
+        board.food.clear();
+        let template = board.snakes[0].clone();
+        board.snakes = snakes
+            .iter()
+            .map(|(id, _)| Battlesnake {
+                id: id.to_string(),
+                ..template.clone()
+            })
+            .collect();
+        for (index, (_, body)) in snakes.iter().enumerate() {
+            place(&mut board, index, body);
+        }
+        board
+    }
 }
@@ -1,3 +1,4 @@
+use serde::Serializer;
 use std::str::FromStr;
 
 use crate::models::{Battlesnake, Board};
@@ -20,6 +21,14 @@ pub fn dir_to_string(tup: (i32, i32)) -> String {
     panic!("invalid direction");
 }
 
+// For `#[serde(serialize_with)]`, writes a direction by its name.
+pub fn serialize_dir<S: Serializer>(
+    dir: &(i32, i32),
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&dir_to_string(*dir))
+}
+
 pub fn fix_snake_order(board: &mut Board, starting_snake: Battlesnake) {
     let starting_snake_id = starting_snake.id.clone();
     let mut new_snakes = vec![starting_snake];
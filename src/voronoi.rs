@@ -0,0 +1,169 @@
+use crate::{
+    models::{Board, Coord},
+    utils,
+};
+
+// Who gets where first. Indexed like the snakes on the board.
+#[derive(Debug)]
+pub struct Territory {
+    // Cells a snake reaches before anybody that could beat it there.
+    pub cells: Vec<usize>,
+    // Food inside those cells.
+    pub food: Vec<usize>,
+    // Cells reached at the same time by snakes of the same length, these
+    // belong to nobody and stop the search.
+    pub contested: usize,
+}
+
+// Best claim on a cell within one step of the search.
+#[derive(Clone, Copy)]
+struct Claim {
+    owner: usize,
+    contested: bool,
+}
+
+// Multi-source BFS from every head at once. Each step claims the cells
+// next to the cells claimed in the step before, when several snakes arrive
+// together the longest one wins the head-to-head and takes the cell.
+pub fn voronoi(board: &Board) -> Territory {
+    let width = board.width as usize;
+    let height = board.height as usize;
+    let index = |coord: &Coord| coord.y() * width + coord.x();
+
+    let mut visited = vec![false; width * height];
+    let mut has_food = vec![false; width * height];
+    for food in &board.food {
+        has_food[index(food)] = true;
+    }
+    let mut frontier = vec![];
+    for (owner, snake) in board.live_snakes() {
+        for coord in &snake.body {
+            visited[index(coord)] = true;
+        }
+        frontier.push((snake.head.clone(), owner));
+    }
+
+    let mut territory = Territory {
+        cells: vec![0; board.snakes.len()],
+        food: vec![0; board.snakes.len()],
+        contested: 0,
+    };
+    let mut claims: Vec<Option<Claim>> = vec![None; width * height];
+    while !frontier.is_empty() {
+        let mut reached = vec![];
+        for (from, owner) in &frontier {
+            for (y, x) in utils::DIRECTIONS {
+                let next = Coord {
+                    x: from.x + x,
+                    y: from.y + y,
+                };
+                if !next.in_bounds(board.width(), board.height())
+                    || visited[index(&next)]
+                {
+                    continue;
+                }
+                let cell = index(&next);
+                claims[cell] = match claims[cell] {
+                    None => {
+                        reached.push(next);
+                        Some(Claim {
+                            owner: *owner,
+                            contested: false,
+                        })
+                    }
+                    Some(claim) => Some(stronger_claim(board, claim, *owner)),
+                };
+            }
+        }
+
+        let mut next_frontier = vec![];
+        for coord in reached {
+            let cell = index(&coord);
+            visited[cell] = true;
+            let claim = claims[cell].unwrap();
+            if claim.contested {
+                territory.contested += 1;
+                continue;
+            }
+            territory.cells[claim.owner] += 1;
+            if has_food[cell] {
+                territory.food[claim.owner] += 1;
+            }
+            next_frontier.push((coord, claim.owner));
+        }
+        frontier = next_frontier;
+    }
+    territory
+}
+
+fn stronger_claim(board: &Board, claim: Claim, owner: usize) -> Claim {
+    let claim_length = board.snakes[claim.owner].body.len();
+    let length = board.snakes[owner].body.len();
+    if length > claim_length {
+        Claim {
+            owner,
+            contested: false,
+        }
+    } else if length == claim_length && owner != claim.owner {
+        Claim {
+            owner: claim.owner,
+            contested: true,
+        }
+    } else {
+        claim
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::scenarios::{
+        get_scenario, FOUR_SNAKES, GET_THE_FOOD,
+    };
+
+    #[test]
+    fn test_free_cells_are_counted_at_most_once() {
+        let board = get_scenario(FOUR_SNAKES).board;
+        let territory = voronoi(&board);
+        let bodies: usize =
+            board.snakes.iter().map(|snake| snake.body.len()).sum();
+        let free = (board.width * board.height) as usize - bodies;
+        let claimed: usize = territory.cells.iter().sum();
+        // Contested cells can cut off cells nobody reaches.
+        assert!(claimed + territory.contested <= free);
+        assert!(territory.food.iter().sum::<usize>() <= board.food.len());
+    }
+
+    #[test]
+    fn test_longer_snake_wins_ties() {
+        let mut board = get_scenario(FOUR_SNAKES).board;
+        board.snakes.truncate(2);
+        // "you" at (1, 5) and "big" at (1, 7) reach the cell in between at
+        // the same time, food there shows who owns it.
+        board.food = vec![Coord { x: 1, y: 6 }];
+        let length = board.snakes[0].body.len();
+        board.snakes[1].body.truncate(length);
+        let tied = voronoi(&board);
+        assert!(tied.contested > 0);
+        assert_eq!(tied.food, [0, 0]);
+
+        // One longer without taking up another cell, like after eating.
+        let tail = board.snakes[1].body[length - 1].clone();
+        board.snakes[1].body.push(tail);
+        let territory = voronoi(&board);
+        assert_eq!(territory.contested, 0);
+        assert_eq!(territory.food, [0, 1]);
+        assert!(territory.cells[1] >= tied.cells[1] + tied.contested);
+        assert!(territory.cells[0] <= tied.cells[0]);
+    }
+
+    #[test]
+    fn test_food_owned_by_closest_snake() {
+        let board = get_scenario(GET_THE_FOOD).board;
+        let territory = voronoi(&board);
+        // We are next to (9, 6) and closer to (10, 4), the other snake is
+        // closer to (5, 5).
+        assert_eq!(territory.food, [2, 1]);
+    }
+}